@@ -0,0 +1,55 @@
+// cargo bench --bench scan [path to a dumped module]
+use gglibrary::signature::Signature;
+use std::time::Instant;
+
+const SIGNATURES: &[&str] = &[
+    "48 89 5c 24 ? 48 89 74 24 ? 48 89 7c 24 ? 55 41 54 41 55 41 56 41 57 48 8d 6c 24 ? 48 81 ec ? ? ? ? 48 8b 05 ? ? ? ? 48 33 c4 48 89 45 ? c6 05",
+    "4d 8b c8 4c 8b c2 8b d1 48 8b 0d ? ? ? ? e9",
+    "83 fa ? 76 ? 83 fa ? 75",
+    "48 89 4c 24 ? 48 83 ec ? ff 15",
+];
+
+// xorshift so the fixture is the same every run
+fn fixture(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x9e3779b97f4a7c15;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn naive(sig: &Signature, haystack: &[u8]) -> Option<usize> {
+    haystack.windows(sig.len()).position(|w| sig.matches(w))
+}
+
+fn main() {
+    let haystack = match std::env::args().skip(1).find(|arg| !arg.starts_with("--")) {
+        Some(path) => std::fs::read(path).unwrap(),
+        None => fixture(128 * 1024 * 1024),
+    };
+    println!("haystack: {} bytes", haystack.len());
+    for pattern in SIGNATURES {
+        let sig = Signature::from_ida(pattern).unwrap();
+
+        let start = Instant::now();
+        let found = sig.scan(&haystack);
+        let scan_time = start.elapsed();
+
+        let start = Instant::now();
+        let expected = naive(&sig, &haystack);
+        let naive_time = start.elapsed();
+
+        assert_eq!(found, expected);
+        println!(
+            "{:>3} bytes  scan {:>10.2?}  naive {:>10.2?}  {:?}",
+            sig.len(),
+            scan_time,
+            naive_time,
+            found
+        );
+    }
+}
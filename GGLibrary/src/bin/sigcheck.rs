@@ -0,0 +1,70 @@
+// sigcheck <GGST-Win64-Shipping.exe> <signatures.toml>...
+// resolves every signature a mod declares against an exe on disk, exits non-zero if any failed
+use gglibrary::cache::AddressCache;
+use gglibrary::pe::PeImage;
+use gglibrary::sigtable::{ResolveError, SignatureTable};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [exe, tables @ ..] = args.as_slice() else {
+        eprintln!("usage: sigcheck <GGST-Win64-Shipping.exe> <signatures.toml>...");
+        return ExitCode::FAILURE;
+    };
+    if tables.is_empty() {
+        eprintln!("usage: sigcheck <GGST-Win64-Shipping.exe> <signatures.toml>...");
+        return ExitCode::FAILURE;
+    }
+
+    let pe = match PeImage::load(exe) {
+        Ok(pe) => pe,
+        Err(err) => {
+            eprintln!("failed to load {}: {}", exe, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "{} (timestamp {:#010x}, checksum {:#010x})",
+        exe, pe.headers.timestamp, pe.headers.checksum
+    );
+
+    let mut failed = 0;
+    for path in tables {
+        println!("\n{}", path);
+        let table = match std::fs::read_to_string(path) {
+            Ok(s) => SignatureTable::from_toml(&s),
+            Err(err) => {
+                println!("  failed to read: {}", err);
+                failed += 1;
+                continue;
+            }
+        };
+        let table = match table {
+            Ok(table) => table,
+            Err(err) => {
+                println!("  {}", err);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let results = table.resolve_batch(&pe.image, &mut AddressCache::default());
+        for (name, result) in results {
+            match &result {
+                Ok(rva) => println!("  {:<10} {:#010x}  {}", ResolveError::status(&result), rva, name),
+                Err(err) => {
+                    failed += 1;
+                    println!("  {:<10} {:10}  {} ({})", ResolveError::status(&result), "", name, err);
+                }
+            }
+        }
+    }
+
+    if failed == 0 {
+        println!("\nall signatures resolved");
+        ExitCode::SUCCESS
+    } else {
+        println!("\n{} signature(s) failed", failed);
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,59 @@
+// sigmaker <GGST-Win64-Shipping.exe> <rva>...
+// prints the shortest unique signature for each function, ready to paste into signatures.toml
+use gglibrary::pe::PeImage;
+use gglibrary::sigmaker::make_signature;
+use std::process::ExitCode;
+
+// longer than any signature worth keeping
+const MAX_LEN: usize = 256;
+
+fn parse_rva(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => usize::from_str_radix(s, 16).ok(),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [exe, rvas @ ..] = args.as_slice() else {
+        eprintln!("usage: sigmaker <GGST-Win64-Shipping.exe> <rva>...");
+        return ExitCode::FAILURE;
+    };
+    if rvas.is_empty() {
+        eprintln!("usage: sigmaker <GGST-Win64-Shipping.exe> <rva>...");
+        return ExitCode::FAILURE;
+    }
+
+    let pe = match PeImage::load(exe) {
+        Ok(pe) => pe,
+        Err(err) => {
+            eprintln!("failed to load {}: {}", exe, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let relocations = match pe.relocations() {
+        Ok(relocations) => relocations,
+        Err(err) => {
+            eprintln!("failed to read relocations, not masking them: {}", err);
+            Vec::new()
+        }
+    };
+
+    let mut failed = 0;
+    for arg in rvas {
+        let Some(rva) = parse_rva(arg) else {
+            println!("{}: not a hex rva", arg);
+            failed += 1;
+            continue;
+        };
+        match make_signature(&pe.image, &relocations, rva, MAX_LEN) {
+            Ok(sig) => println!("{:#010x}  pattern = \"{}\"", rva, sig),
+            Err(err) => {
+                println!("{:#010x}  {}", rva, err);
+                failed += 1;
+            }
+        }
+    }
+    if failed == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
@@ -0,0 +1,144 @@
+// sigmatrix <signatures.toml> <GGST-Win64-Shipping.exe>...
+// checks every signature of a table against several builds of the game, then suggests a new
+// signature for the ones that broke, generated from a build where they still resolve
+use gglibrary::cache::AddressCache;
+use gglibrary::pe::PeImage;
+use gglibrary::sigmaker::make_signature;
+use gglibrary::sigtable::{ResolveError, SignatureTable};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::process::ExitCode;
+
+const MAX_LEN: usize = 256;
+
+struct Build {
+    path: String,
+    pe: PeImage,
+    relocations: Vec<Range<usize>>,
+    // entry name -> where its pattern matched, before operands
+    matches: BTreeMap<String, usize>,
+    results: BTreeMap<String, Result<usize, ResolveError>>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [table_path, exes @ ..] = args.as_slice() else {
+        eprintln!("usage: sigmatrix <signatures.toml> <GGST-Win64-Shipping.exe>...");
+        return ExitCode::FAILURE;
+    };
+    if exes.is_empty() {
+        eprintln!("usage: sigmatrix <signatures.toml> <GGST-Win64-Shipping.exe>...");
+        return ExitCode::FAILURE;
+    }
+
+    let table = match std::fs::read_to_string(table_path).map(|s| SignatureTable::from_toml(&s)) {
+        Ok(Ok(table)) => table,
+        Ok(Err(err)) => {
+            eprintln!("{}: {}", table_path, err);
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("failed to read {}: {}", table_path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut builds = Vec::new();
+    for path in exes {
+        let pe = match PeImage::load(path) {
+            Ok(pe) => pe,
+            Err(err) => {
+                eprintln!("failed to load {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        let relocations = pe.relocations().unwrap_or_default();
+        // the cache ends up holding every match offset, that's what signatures get regenerated from
+        let mut cache = AddressCache::default();
+        let results: BTreeMap<String, _> = table
+            .resolve_batch(&pe.image, &mut cache)
+            .into_iter()
+            .map(|(name, result)| (name.to_string(), result))
+            .collect();
+        let matches = results.keys().filter_map(|name| Some((name.clone(), cache.get(name)?))).collect();
+        builds.push(Build { path: path.clone(), pe, relocations, matches, results });
+    }
+    // oldest first so the matrix reads left to right
+    builds.sort_by_key(|build| build.pe.headers.timestamp);
+
+    println!("builds:");
+    for (i, build) in builds.iter().enumerate() {
+        println!("  [{}] {} (timestamp {:#010x})", i, build.path, build.pe.headers.timestamp);
+    }
+
+    let width = table.entries().iter().map(|entry| entry.name.len()).max().unwrap_or(0);
+    print!("\n{:width$}", "");
+    for i in 0..builds.len() {
+        print!("  {:<10}", format!("[{}]", i));
+    }
+    println!();
+    for entry in table.entries() {
+        print!("{:width$}", entry.name);
+        for build in &builds {
+            print!("  {:<10}", ResolveError::status(&build.results[&entry.name]));
+        }
+        println!();
+    }
+
+    let broken: Vec<&str> = table
+        .entries()
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .filter(|name| builds.iter().any(|build| build.results[*name].is_err()))
+        .collect();
+    if broken.is_empty() {
+        println!("\nevery signature resolved in every build");
+        return ExitCode::SUCCESS;
+    }
+
+    println!("\nsuggested signatures:");
+    for name in &broken {
+        // try every build it still works in, newest first, keep whichever is unique in the most builds
+        let mut best: Option<(usize, String, Vec<usize>)> = None;
+        for (i, build) in builds.iter().enumerate().rev() {
+            let Some(&offset) = build.matches.get(*name).filter(|_| build.results[*name].is_ok()) else {
+                continue;
+            };
+            let Ok(sig) = make_signature(&build.pe.image, &build.relocations, offset, MAX_LEN) else {
+                continue;
+            };
+            let unique_in: Vec<usize> = builds
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    let text = other.pe.headers.code_range(other.pe.image.len());
+                    sig.scan_unique_in(&other.pe.image, text).is_ok()
+                })
+                .map(|(j, _)| j)
+                .collect();
+            if best.as_ref().is_none_or(|(_, _, best)| unique_in.len() > best.len()) {
+                best = Some((i, sig.to_string(), unique_in));
+            }
+        }
+        match best {
+            Some((from, pattern, unique_in)) => {
+                let unique_in: Vec<String> = unique_in.iter().map(|i| format!("[{}]", i)).collect();
+                println!("  {} (made from [{}], unique in {})", name, from, unique_in.join(" "));
+                println!("    pattern = \"{}\"", pattern);
+                let entry = table.get(name).unwrap();
+                if entry.sig.to_string() == pattern {
+                    println!("    (same as now, the code itself changed in the other builds)");
+                }
+                // it's unique on its own, no need to search after anything
+                if entry.from.is_some() {
+                    println!("    (drop `from`, keep `operands`)");
+                }
+            }
+            None => println!("  {} (doesn't resolve in any build, nothing to make one from)", name),
+        }
+    }
+
+    // only the newest build decides whether the table needs fixing
+    let newest = builds.last().unwrap();
+    if broken.iter().any(|name| newest.results[*name].is_err()) { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -0,0 +1,60 @@
+// snapdiff [--width N] <snapshot.toml> <snapshot.toml>...
+// lists every offset that changed between snapshots of the same struct, with the value in each
+use gglibrary::snapshot::{changed_offsets, describe, Snapshot};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: snapdiff [--width N] <snapshot.toml> <snapshot.toml>...";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut width = 4;
+    if let Some(i) = args.iter().position(|arg| arg == "--width") {
+        match args.get(i + 1).and_then(|n| n.parse().ok()).filter(|&n| n > 0) {
+            Some(n) => width = n,
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+        args.drain(i..i + 2);
+    }
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let mut snapshots = Vec::new();
+    let mut buffers = Vec::new();
+    for path in &args {
+        let snapshot = match Snapshot::load(path) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                eprintln!("failed to load {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        match snapshot.bytes() {
+            Ok(bytes) => buffers.push(bytes),
+            Err(err) => {
+                eprintln!("bad data in {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+        println!("{}  {} at {:#x}, {} bytes", path, snapshot.label, snapshot.addr, buffers.last().unwrap().len());
+        snapshots.push(snapshot);
+    }
+    if buffers.iter().any(|bytes| bytes.len() != buffers[0].len()) {
+        println!("sizes differ, only comparing the common prefix");
+    }
+
+    let changed = changed_offsets(&buffers, width);
+    for &offset in &changed {
+        println!("\n+{:#x}", offset);
+        for (path, bytes) in args.iter().zip(&buffers) {
+            let end = (offset + width).min(bytes.len());
+            println!("  {}  {}", path, describe(&bytes[offset..end]));
+        }
+    }
+    println!("\n{} changed", changed.len());
+    ExitCode::SUCCESS
+}
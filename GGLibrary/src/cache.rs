@@ -0,0 +1,162 @@
+use crate::pe::PeHeaders;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Identifies one build of the game, any patch changes at least one of these
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub timestamp: u32,
+    pub checksum: u32,
+    pub size_of_image: u32,
+}
+
+impl CacheKey {
+    pub fn of(headers: &PeHeaders) -> Self {
+        Self {
+            timestamp: headers.timestamp,
+            checksum: headers.checksum,
+            size_of_image: headers.size_of_image,
+        }
+    }
+}
+
+/// Signature match offsets from a previous run against the same build. The match is stored
+/// rather than the resolved address so a cached entry can be checked with one byte compare and
+/// the operands reapplied.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AddressCache {
+    key: CacheKey,
+    matches: BTreeMap<String, usize>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl AddressCache {
+    pub fn new(key: CacheKey) -> Self {
+        Self { key, ..Default::default() }
+    }
+
+    /// Starts over if the file is missing, unreadable or from another build
+    pub fn load(path: impl AsRef<Path>, key: CacheKey) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str::<AddressCache>(&s).ok())
+            .filter(|cache| cache.key == key)
+            .unwrap_or_else(|| Self::new(key))
+    }
+
+    pub fn save(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let s = toml::to_string(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, s)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn key(&self) -> CacheKey {
+        self.key
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.matches.get(name).copied()
+    }
+
+    pub fn insert(&mut self, name: &str, offset: usize) {
+        if self.matches.insert(name.to_string(), offset) != Some(offset) {
+            self.dirty = true;
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if self.matches.remove(name).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Changed since it was loaded or last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const KEY: CacheKey = CacheKey { timestamp: 0x6650_0000, checksum: 0x1234, size_of_image: 0x500_0000 };
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gglibrary-{}-{}.toml", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_file("round_trip");
+        let mut cache = AddressCache::new(KEY);
+        cache.insert("RED_SaveData", 0x1234);
+        cache.save(&path).unwrap();
+
+        let loaded = AddressCache::load(&path, KEY);
+        assert_eq!(loaded.key(), KEY);
+        assert_eq!(loaded.get("RED_SaveData"), Some(0x1234));
+        assert!(!loaded.is_dirty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn other_builds_start_over() {
+        let path = temp_file("other_builds");
+        let mut cache = AddressCache::new(KEY);
+        cache.insert("RED_SaveData", 0x1234);
+        cache.save(&path).unwrap();
+
+        for key in [
+            CacheKey { timestamp: KEY.timestamp + 1, ..KEY },
+            CacheKey { checksum: KEY.checksum + 1, ..KEY },
+            CacheKey { size_of_image: KEY.size_of_image + 0x1000, ..KEY },
+        ] {
+            let loaded = AddressCache::load(&path, key);
+            assert_eq!(loaded.key(), key);
+            assert_eq!(loaded.get("RED_SaveData"), None);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unreadable_files_start_over() {
+        let path = temp_file("unreadable");
+        std::fs::write(&path, "matches = [not toml").unwrap();
+        let loaded = AddressCache::load(&path, KEY);
+        assert_eq!(loaded.key(), KEY);
+        assert_eq!(loaded.get("RED_SaveData"), None);
+        assert!(!loaded.is_dirty());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(AddressCache::load(&path, KEY).get("RED_SaveData"), None);
+    }
+
+    #[test]
+    fn dirty_only_on_change() {
+        let path = temp_file("dirty");
+        let mut cache = AddressCache::new(KEY);
+        assert!(!cache.is_dirty());
+        cache.remove("RED_SaveData");
+        assert!(!cache.is_dirty());
+
+        cache.insert("RED_SaveData", 0x1234);
+        assert!(cache.is_dirty());
+        cache.save(&path).unwrap();
+        assert!(!cache.is_dirty());
+
+        cache.insert("RED_SaveData", 0x1234);
+        assert!(!cache.is_dirty());
+        cache.insert("RED_SaveData", 0x5678);
+        assert!(cache.is_dirty());
+        cache.save(&path).unwrap();
+
+        cache.remove("RED_SaveData");
+        assert!(cache.is_dirty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
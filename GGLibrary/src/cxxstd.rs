@@ -1,8 +1,9 @@
 use crate::output::budget_log;
 use libc::{free, malloc, memcpy, memset};
 use std::fmt;
+use std::mem::ManuallyDrop;
 use std::ops::Deref;
-use widestring::U16String;
+use widestring::{U16Str, U16String};
 
 const BUF_SIZE: usize = 16;
 const SMALL_STRING_SIZE: usize = (BUF_SIZE - 1) / size_of::<u16>(); // 7
@@ -50,16 +51,20 @@ impl CxxString {
         string.length = wstring.len();
         string.capacity = wstring.len();
         if string.length <= SMALL_STRING_SIZE {
-            std::ptr::copy_nonoverlapping::<u8>(
-                wstring.as_ptr().cast(),
-                string.data.small.as_mut_ptr(),
-                nbytes - 1,
-            );
+            unsafe {
+                std::ptr::copy_nonoverlapping::<u8>(
+                    wstring.as_ptr().cast(),
+                    string.data.small.as_mut_ptr(),
+                    nbytes - 1,
+                );
+            }
         } else {
-            let ptr = malloc(nbytes); // msvc new uses malloc so...
-            memset(ptr, 0, nbytes); // set null terminator
-            memcpy(ptr, wstring.as_ptr().cast(), nbytes - 1);
-            string.data.large = ptr.cast();
+            unsafe {
+                let ptr = malloc(nbytes); // msvc new uses malloc so...
+                memset(ptr, 0, nbytes); // set null terminator
+                memcpy(ptr, wstring.as_ptr().cast(), nbytes - 1);
+                string.data.large = ptr.cast();
+            }
         }
         string
     }
@@ -79,6 +84,12 @@ impl CxxString {
     }
 }
 
+impl Default for CxxString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for CxxString {
     fn drop(&mut self) {
         if self.length > SMALL_STRING_SIZE {
@@ -87,9 +98,11 @@ impl Drop for CxxString {
     }
 }
 
+#[repr(C)]
 pub struct CxxVector<T> {
     first: *const T,
     last: *const T,
+    #[allow(dead_code)]
     end: *const T,
 }
 
@@ -111,6 +124,7 @@ impl<'a, T> IntoIterator for &'a CxxVector<T> {
     }
 }
 
+#[repr(C)]
 pub struct CxxUniquePtr<T> {
     pub ptr: *mut T,
 }
@@ -139,13 +153,20 @@ impl CxxStringView {
         string.length = s.len();
         let wstring = U16String::from_str(s);
         let nbytes = wstring.len() * size_of::<u16>() + 1;
-        let ptr = malloc(nbytes);
-        memset(ptr, 0, nbytes);
-        memcpy(ptr, wstring.as_ptr().cast(), nbytes - 1);
-        string.data = ptr.cast();
+        unsafe {
+            let ptr = malloc(nbytes);
+            memset(ptr, 0, nbytes);
+            memcpy(ptr, wstring.as_ptr().cast(), nbytes - 1);
+            string.data = ptr.cast();
+        }
         string
     }
 
+    /// A view into `s` like a real string_view, it doesn't own anything so it can't be dropped
+    pub fn borrowed(s: &U16Str) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(Self { data: s.as_ptr().cast_mut(), length: s.len() })
+    }
+
     pub fn string(&self) -> String {
         unsafe { U16String::from_ptr(self.data, self.length) }.to_string_lossy()
     }
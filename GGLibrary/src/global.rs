@@ -0,0 +1,211 @@
+use log::{error, info, warn};
+use crate::pointer::PointerPath;
+use crate::region::{default_regions, ReadError};
+use crate::sigtable::SignatureTable;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+/// A game global of type `T`, found through a `PointerPath` the first time it's used:
+///
+/// ```ignore
+/// static CHARA_HISTORY: GameGlobal<SDecideInfoHistory> =
+///     GameGlobal::new("CharaHistory", r#"entry("CharaHistory")"#, || TABLE.as_ref())
+///         .validate(|history| history.is_valid == 1);
+/// ```
+///
+/// `T` gets made out of whatever bytes are there before `validate` sees it, so like with
+/// `region::try_read` it shouldn't have `bool`s or enums.
+///
+/// Everything before the path's first `->` is resolved once and cached (and logged), the rest
+/// is followed on every `get` since the pointers it reads can change. In game the path can be
+/// overridden from the mod's `paths.toml` under `name`.
+pub struct GameGlobal<T> {
+    name: &'static str,
+    path: &'static str,
+    table: fn() -> Option<&'static SignatureTable>,
+    validate: fn(&T) -> bool,
+    // the path and the live address of its static part, `None` if that failed
+    resolved: OnceLock<Option<(PointerPath, usize)>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> GameGlobal<T> {
+    /// `table` is only asked for when the path starts with an `entry`
+    pub const fn new(name: &'static str, path: &'static str, table: fn() -> Option<&'static SignatureTable>) -> Self {
+        Self { name, path, table, validate: |_| true, resolved: OnceLock::new(), _marker: PhantomData }
+    }
+
+    /// `get` only returns values `validate` is happy with
+    pub const fn validate(mut self, validate: fn(&T) -> bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn path(&self) -> Option<PointerPath> {
+        #[cfg(windows)]
+        let path = crate::memory::pointer_path(self.name, self.path);
+        #[cfg(not(windows))]
+        let path = self.path.parse::<PointerPath>();
+        path.inspect_err(|err| error!("bad path for {}: {}", self.name, err)).ok()
+    }
+
+    fn resolve(&self) -> Option<(PointerPath, usize)> {
+        let path = self.path()?;
+        #[cfg(windows)]
+        {
+            match crate::memory::resolve_path_static(&path, (self.table)()) {
+                Ok(addr) => {
+                    info!("{} at {:p} ({})", self.name, addr, path);
+                    Some((path, addr as usize))
+                }
+                Err(err) => {
+                    warn!("failed to resolve {}: {}", self.name, err);
+                    None
+                }
+            }
+        }
+        // nothing to scan, only `init_at` works here
+        #[cfg(not(windows))]
+        {
+            let _ = (path, self.table);
+            None
+        }
+    }
+
+    /// Skips the scan, `addr` is where the static part of the path already is. `false` if it was
+    /// already resolved.
+    pub fn init_at(&self, addr: usize) -> bool {
+        let Some(path) = self.path() else {
+            return false;
+        };
+        let set = self.resolved.set(Some((path, addr))).is_ok();
+        if set {
+            info!("{} at {:#x}", self.name, addr);
+        }
+        set
+    }
+
+    /// Never resolve, e.g. when a sanity check on the address failed
+    pub fn disable(&self) {
+        let _ = self.resolved.set(None);
+    }
+
+    /// Live address of the global, `None` if it didn't resolve or a pointer on the way isn't
+    /// readable. Doesn't validate.
+    pub fn addr(&self) -> Option<*mut T> {
+        let (path, addr) = self.resolved.get_or_init(|| self.resolve()).as_ref()?;
+        match path.follow(*addr, default_regions()) {
+            Ok(addr) => Some(addr as *mut T),
+            Err(err) => {
+                warn!("{}: {}", self.name, err);
+                None
+            }
+        }
+    }
+
+    /// The global if all of it is readable and it passes validation. Nothing stops the game from
+    /// writing to it at the same time.
+    pub unsafe fn get(&self) -> Option<&T> {
+        let addr = self.checked(|addr| default_regions().check_readable(addr, size_of::<T>()))?;
+        Some(unsafe { &*addr })
+    }
+
+    /// `get` for writing, all of it has to be writable as well
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut(&self) -> Option<&mut T> {
+        let addr = self.checked(|addr| {
+            let regions = default_regions();
+            regions.check_readable(addr, size_of::<T>()).and_then(|()| regions.check_writable(addr, size_of::<T>()))
+        })?;
+        Some(unsafe { &mut *addr })
+    }
+
+    fn checked(&self, check: impl FnOnce(usize) -> Result<(), ReadError>) -> Option<*mut T> {
+        let addr = self.addr()?;
+        if let Err(err) = check(addr as usize) {
+            warn!("{}: {}", self.name, err);
+            return None;
+        }
+        (self.validate)(unsafe { &*addr }).then_some(addr)
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use crate::region::FAKE_REGIONS;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct History {
+        color: u32,
+        is_valid: u8,
+    }
+
+    // leaked so the fake region never points at memory something else gets
+    fn mapped(value: History, insert: impl Fn(usize, usize)) -> *mut History {
+        let value = Box::into_raw(Box::new(value));
+        insert(value as usize, value as usize + size_of::<History>());
+        value
+    }
+
+    #[test]
+    fn checks_regions_and_validates() {
+        static GLOBAL: GameGlobal<History> =
+            GameGlobal::new("History", r#"entry("History")"#, || None).validate(|history| history.is_valid == 1);
+        let history = mapped(History { color: 3, is_valid: 1 }, |start, end| FAKE_REGIONS.insert(start, end, true));
+        assert!(GLOBAL.init_at(history as usize));
+        assert!(!GLOBAL.init_at(0x1000));
+
+        assert_eq!(unsafe { GLOBAL.get() }.map(|history| history.color), Some(3));
+        unsafe { GLOBAL.get_mut() }.unwrap().color = 4;
+        assert_eq!(unsafe { (*history).color }, 4);
+        // anything but 1 is invalid, not UB
+        unsafe { (*history).is_valid = 2 };
+        assert!(unsafe { GLOBAL.get() }.is_none());
+        assert!(unsafe { GLOBAL.get_mut() }.is_none());
+    }
+
+    #[test]
+    fn read_only_globals_arent_mut() {
+        static GLOBAL: GameGlobal<History> = GameGlobal::new("ReadOnly", r#"entry("ReadOnly")"#, || None);
+        let history = mapped(History { color: 5, is_valid: 0 }, |start, end| FAKE_REGIONS.insert_read_only(start, end));
+        GLOBAL.init_at(history as usize);
+        assert_eq!(unsafe { GLOBAL.get() }.map(|history| history.color), Some(5));
+        assert!(unsafe { GLOBAL.get_mut() }.is_none());
+    }
+
+    #[test]
+    fn follows_pointers_every_get() {
+        static GLOBAL: GameGlobal<History> = GameGlobal::new("Behind", r#"entry("Behind") -> +0x8"#, || None);
+        let first = mapped(History { color: 6, is_valid: 0 }, |start, end| FAKE_REGIONS.insert(start, end, true));
+        // where the static part of the path points, holds a pointer 8 bytes before the global
+        let slot: &'static mut [usize; 1] = Box::leak(Box::new([0]));
+        FAKE_REGIONS.insert_slice(slot.as_slice(), true);
+        GLOBAL.init_at(slot.as_ptr() as usize);
+        assert!(unsafe { GLOBAL.get() }.is_none());
+        assert_eq!(GLOBAL.addr(), None);
+
+        slot[0] = first as usize - 8;
+        assert_eq!(unsafe { GLOBAL.get() }.map(|history| history.color), Some(6));
+        // unmapped memory isn't touched
+        slot[0] = 0x10;
+        assert_eq!(GLOBAL.addr(), Some(0x18 as *mut History));
+        assert!(unsafe { GLOBAL.get() }.is_none());
+    }
+
+    #[test]
+    fn disabled_never_resolves() {
+        static GLOBAL: GameGlobal<History> = GameGlobal::new("Disabled", r#"entry("Disabled")"#, || None);
+        GLOBAL.disable();
+        assert!(!GLOBAL.init_at(0x1000));
+        assert_eq!(GLOBAL.addr(), None);
+        // nothing to scan off Windows either
+        static UNRESOLVED: GameGlobal<History> = GameGlobal::new("Unresolved", r#"entry("Unresolved")"#, || None);
+        assert_eq!(UNRESOLVED.addr(), None);
+    }
+}
@@ -0,0 +1,810 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use log::error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HookError {
+    AlreadyCreated,
+    NotCreated,
+    AlreadyEnabled,
+    NotEnabled,
+    /// anything else the backend reports
+    Backend(String),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::AlreadyCreated => write!(f, "hook already created"),
+            HookError::NotCreated => write!(f, "hook not created"),
+            HookError::AlreadyEnabled => write!(f, "hook already enabled"),
+            HookError::NotEnabled => write!(f, "hook not enabled"),
+            HookError::Backend(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// Whatever actually patches functions. Every `Hook` remembers the backend that created it, so
+/// enabling, disabling and removing go back to the same one.
+pub trait HookBackend: Sync {
+    /// Redirects `target` to `detour` without enabling it, returns the pointer that calls the
+    /// original
+    unsafe fn create(&self, target: *mut c_void, detour: *mut c_void) -> Result<*mut c_void, HookError>;
+    unsafe fn enable(&self, target: *mut c_void) -> Result<(), HookError>;
+    unsafe fn disable(&self, target: *mut c_void) -> Result<(), HookError>;
+    unsafe fn remove(&self, target: *mut c_void) -> Result<(), HookError>;
+}
+
+#[cfg(windows)]
+pub struct MinHookBackend;
+
+#[cfg(windows)]
+impl From<minhook::MH_STATUS> for HookError {
+    fn from(status: minhook::MH_STATUS) -> HookError {
+        use minhook::MH_STATUS;
+        match status {
+            MH_STATUS::MH_ERROR_ALREADY_CREATED => HookError::AlreadyCreated,
+            MH_STATUS::MH_ERROR_NOT_CREATED => HookError::NotCreated,
+            MH_STATUS::MH_ERROR_ENABLED => HookError::AlreadyEnabled,
+            MH_STATUS::MH_ERROR_DISABLED => HookError::NotEnabled,
+            status => HookError::Backend(format!("{:?}", status)),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl HookBackend for MinHookBackend {
+    unsafe fn create(&self, target: *mut c_void, detour: *mut c_void) -> Result<*mut c_void, HookError> {
+        Ok(unsafe { minhook::MinHook::create_hook(target, detour) }?)
+    }
+
+    unsafe fn enable(&self, target: *mut c_void) -> Result<(), HookError> {
+        Ok(unsafe { minhook::MinHook::enable_hook(target) }?)
+    }
+
+    unsafe fn disable(&self, target: *mut c_void) -> Result<(), HookError> {
+        Ok(unsafe { minhook::MinHook::disable_hook(target) }?)
+    }
+
+    unsafe fn remove(&self, target: *mut c_void) -> Result<(), HookError> {
+        Ok(unsafe { minhook::MinHook::remove_hook(target) }?)
+    }
+}
+
+/// MinHook in the game, the global mock everywhere else so hooks can be created in tests
+pub fn default_backend() -> &'static dyn HookBackend {
+    #[cfg(windows)]
+    {
+        &MinHookBackend
+    }
+    #[cfg(not(windows))]
+    {
+        &MOCK_BACKEND
+    }
+}
+
+#[cfg(not(windows))]
+pub static MOCK_BACKEND: MockBackend = MockBackend::new();
+
+/// Everything a `MockBackend` was asked to do, addresses as `usize`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockCall {
+    Create { target: usize, detour: usize },
+    Enable(usize),
+    Disable(usize),
+    Remove(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct MockHook {
+    detour: usize,
+    enabled: bool,
+}
+
+/// Patches nothing, it only tracks hook state and records calls. The "original" it hands back
+/// is the target itself, so a detour calling `orig` runs the fake target directly.
+pub struct MockBackend {
+    hooks: Mutex<BTreeMap<usize, MockHook>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockBackend {
+    pub const fn new() -> Self {
+        Self { hooks: Mutex::new(BTreeMap::new()), calls: Mutex::new(Vec::new()) }
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn clear_calls(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    pub fn is_created(&self, target: *mut c_void) -> bool {
+        self.hooks.lock().unwrap().contains_key(&(target as usize))
+    }
+
+    pub fn is_enabled(&self, target: *mut c_void) -> bool {
+        self.hooks.lock().unwrap().get(&(target as usize)).is_some_and(|hook| hook.enabled)
+    }
+
+    pub fn detour(&self, target: *mut c_void) -> Option<*mut c_void> {
+        self.hooks.lock().unwrap().get(&(target as usize)).map(|hook| hook.detour as *mut c_void)
+    }
+
+    /// What a call to `target` would run right now, the detour while enabled and the target
+    /// otherwise
+    pub fn effective(&self, target: *mut c_void) -> *mut c_void {
+        match self.hooks.lock().unwrap().get(&(target as usize)) {
+            Some(hook) if hook.enabled => hook.detour as *mut c_void,
+            _ => target,
+        }
+    }
+
+    fn record(&self, call: MockCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn set_enabled(&self, target: *mut c_void, enabled: bool) -> Result<(), HookError> {
+        let mut hooks = self.hooks.lock().unwrap();
+        let hook = hooks.get_mut(&(target as usize)).ok_or(HookError::NotCreated)?;
+        match (hook.enabled, enabled) {
+            (true, true) => Err(HookError::AlreadyEnabled),
+            (false, false) => Err(HookError::NotEnabled),
+            _ => {
+                hook.enabled = enabled;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HookBackend for MockBackend {
+    unsafe fn create(&self, target: *mut c_void, detour: *mut c_void) -> Result<*mut c_void, HookError> {
+        self.record(MockCall::Create { target: target as usize, detour: detour as usize });
+        let mut hooks = self.hooks.lock().unwrap();
+        if hooks.contains_key(&(target as usize)) {
+            return Err(HookError::AlreadyCreated);
+        }
+        hooks.insert(target as usize, MockHook { detour: detour as usize, enabled: false });
+        Ok(target)
+    }
+
+    unsafe fn enable(&self, target: *mut c_void) -> Result<(), HookError> {
+        self.record(MockCall::Enable(target as usize));
+        self.set_enabled(target, true)
+    }
+
+    unsafe fn disable(&self, target: *mut c_void) -> Result<(), HookError> {
+        self.record(MockCall::Disable(target as usize));
+        self.set_enabled(target, false)
+    }
+
+    unsafe fn remove(&self, target: *mut c_void) -> Result<(), HookError> {
+        self.record(MockCall::Remove(target as usize));
+        self.hooks.lock().unwrap().remove(&(target as usize)).map(|_| ()).ok_or(HookError::NotCreated)
+    }
+}
+
+/// What the registry needs from a hook without knowing its type, implemented by `Hook<T>` and
+/// `VmtHook<F>`
+pub trait RegisteredHook: Send + Sync + 'static {
+    fn enable(&self) -> Result<(), HookError>;
+    fn disable(&self) -> Result<(), HookError>;
+    fn remove(&self) -> Result<(), HookError>;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+/// Every hook one mod created, by name. Each mod keeps its own in a static so enabling,
+/// disabling and uninstalling never touch another mod's hooks the way
+/// `MinHook::enable_all_hooks` does.
+pub struct HookRegistry {
+    hooks: Mutex<BTreeMap<String, Arc<dyn RegisteredHook>>>,
+}
+
+impl HookRegistry {
+    pub const fn new() -> Self {
+        Self { hooks: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Takes ownership of `hook`, fails if `name` is taken
+    pub fn insert<H: RegisteredHook>(&self, name: &str, hook: H) -> Result<Arc<H>, HookError> {
+        let mut hooks = self.hooks.lock().unwrap();
+        if hooks.contains_key(name) {
+            return Err(HookError::AlreadyCreated);
+        }
+        let hook = Arc::new(hook);
+        hooks.insert(name.to_string(), hook.clone());
+        Ok(hook)
+    }
+
+    /// `None` if there's no such hook or it isn't an `H`
+    pub fn get<H: RegisteredHook>(&self, name: &str) -> Option<Arc<H>> {
+        let hook = self.hooks.lock().unwrap().get(name)?.clone();
+        hook.into_any().downcast().ok()
+    }
+
+    /// Unpatches and forgets `name`, like `remove_all` for a single hook
+    pub fn remove(&self, name: &str) -> Result<(), HookError> {
+        let hook = self.hooks.lock().unwrap().remove(name).ok_or(HookError::NotCreated)?;
+        hook.remove()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.hooks.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn enable(&self, name: &str) -> Result<(), HookError> {
+        self.hooks.lock().unwrap().get(name).ok_or(HookError::NotCreated)?.enable()
+    }
+
+    pub fn disable(&self, name: &str) -> Result<(), HookError> {
+        self.hooks.lock().unwrap().get(name).ok_or(HookError::NotCreated)?.disable()
+    }
+
+    /// Enables every hook that isn't already, returns the ones that failed
+    pub fn enable_all(&self) -> Vec<(String, HookError)> {
+        self.for_each(|hook| hook.enable(), HookError::AlreadyEnabled)
+    }
+
+    pub fn disable_all(&self) -> Vec<(String, HookError)> {
+        self.for_each(|hook| hook.disable(), HookError::NotEnabled)
+    }
+
+    /// Unpatches every hook and forgets them, for when the mod is unloaded. Anything still
+    /// holding one of the hooks must not call its `orig` afterwards.
+    pub fn remove_all(&self) -> Vec<(String, HookError)> {
+        let hooks = std::mem::take(&mut *self.hooks.lock().unwrap());
+        hooks
+            .into_iter()
+            .filter_map(|(name, hook)| hook.remove().err().map(|err| (name, err)))
+            .collect()
+    }
+
+    fn for_each(
+        &self,
+        f: impl Fn(&dyn RegisteredHook) -> Result<(), HookError>,
+        ignore: HookError,
+    ) -> Vec<(String, HookError)> {
+        self.hooks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, hook)| match f(hook.as_ref()) {
+                Err(err) if err != ignore => Some((name.clone(), err)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Default for HookRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// For `hooks!`, takes back a hook that got created but couldn't be enabled
+pub fn install_failed(registry: &HookRegistry, name: &str, err: HookError) {
+    error!("failed to install {}: {}", name, err);
+    if let Err(err) = registry.remove(name) {
+        error!("failed to remove {} after that: {}", name, err);
+    }
+}
+
+/// Declares a mod's hooks and the game functions it calls, replacing the hand written typedefs,
+/// `Hooks` struct, `OnceLock` and `HOOKS.set(...)` literal.
+///
+/// ```ignore
+/// gglibrary::hooks! {
+///     pub fn install_hooks;
+///
+///     #[hook(IsAllowedCharaColorID)]
+///     unsafe extern "C" fn is_allowed_chara_color_id(char_id: ECharaID, color_id: EColorID) -> bool {
+///         color_id == EColorID(72) || IsAllowedCharaColorID::orig()(char_id, color_id)
+///     }
+///
+///     #[function]
+///     type FName_ToString = unsafe extern "C" fn(*mut FName, *mut FString);
+/// }
+/// ```
+///
+/// Every `#[hook(Name)]` becomes the detour as written plus a module `Name` with the typedef
+/// (`Name::Type`), `Name::hook()` and `Name::orig()`. Every `#[function]` becomes a module with
+/// `Type` and `get()`. Names are looked up in the map returned by `memory::resolve_signatures`,
+/// so they have to match the mod's `signatures.toml`.
+///
+/// `install_hooks(&registry, &addrs)` stores every function, then creates and enables every hook,
+/// handing them to `registry`. It returns the names that couldn't be installed. A hook is only
+/// enabled once `Name::hook()` works, so a detour the game calls right away can use it.
+///
+/// Detours run under `panic::contain`. A panic is logged and the original gets called instead,
+/// unless the detour already got it from `Name::orig()`: a packet shouldn't be sent twice, so it
+/// returns zero (`false`, `0`, null) instead. So does a detour that panics before its hook is
+/// installed. `#[hook(Name, disable_on_panic)]` also disables the hook so it doesn't panic again
+/// every frame.
+///
+/// While `trace::set_tracing(true)` every call is counted and timed, see `trace::dump`.
+#[macro_export]
+macro_rules! hooks {
+    (@munch $vis:vis $install:ident [$($function:ident)*] [$($hook:ident)*]) => {
+        $vis fn $install(
+            registry: &$crate::hooks::HookRegistry,
+            addrs: &::std::collections::BTreeMap<
+                ::std::string::String,
+                ::std::result::Result<*mut u8, $crate::sigtable::ResolveError>,
+            >,
+        ) -> ::std::result::Result<(), ::std::vec::Vec<&'static str>> {
+            let mut failed = ::std::vec::Vec::new();
+            // functions first, a detour may call one as soon as it's enabled
+            $(
+                if !$function::install(registry, addrs) {
+                    failed.push(stringify!($function));
+                }
+            )*
+            $(
+                if !$hook::install(registry, addrs) {
+                    failed.push(stringify!($hook));
+                }
+            )*
+            if failed.is_empty() { Ok(()) } else { Err(failed) }
+        }
+    };
+
+    (@munch $vis:vis $install:ident $functions:tt $hooks:tt #[hook($target:ident)] $($rest:tt)*) => {
+        $crate::hooks!(@munch $vis $install $functions $hooks #[hook($target, false)] $($rest)*);
+    };
+
+    (@munch $vis:vis $install:ident $functions:tt $hooks:tt #[hook($target:ident, disable_on_panic)] $($rest:tt)*) => {
+        $crate::hooks!(@munch $vis $install $functions $hooks #[hook($target, true)] $($rest)*);
+    };
+
+    // a detour that already called orig doesn't call it again, the game function would run twice
+    (@fallback [$($ret:ty)?] $called:expr, $call:expr) => {
+        if $called {
+            $crate::hooks!(@zero [$($ret)?])
+        } else {
+            $call
+        }
+    };
+
+    // nothing to call, extern "C" returns here are plain data so zero is false, 0 or null
+    (@zero []) => {
+        ()
+    };
+
+    (@zero [$ret:ty]) => {
+        unsafe { ::std::mem::zeroed::<$ret>() }
+    };
+
+    (@munch $vis:vis $install:ident $functions:tt [$($hook:ident)*]
+        #[hook($target:ident, $disable_on_panic:literal)]
+        $(#[$meta:meta])*
+        $fvis:vis unsafe extern "C" fn $detour:ident($($arg:tt: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        // the body is the body of an `unsafe fn`, it's meant to end up in an unsafe block
+        #[allow(clippy::macro_metavars_in_unsafe)]
+        $fvis unsafe extern "C" fn $detour($($arg: $ty),*) $(-> $ret)? {
+            $target::ORIG_CALLED.set(false);
+            let start = $crate::trace::start();
+            #[allow(unused_unsafe)]
+            let result = $crate::panic::contain(stringify!($target), move || unsafe { $body });
+            $crate::trace::finish(&$target::STATS, start);
+            // nothing here may panic again, there's no `contain` left to catch it
+            match (result, $target::HOOK.get()) {
+                (Some(result), _) => result,
+                (None, Some(hook)) => {
+                    if $disable_on_panic {
+                        $crate::panic::disabled_after_panic(stringify!($target), hook.disable());
+                    }
+                    $crate::hooks!(@fallback [$($ret)?] $target::ORIG_CALLED.get(), unsafe { (hook.orig)($($arg),*) })
+                }
+                // called before `install` set it, e.g. `hook()` was what panicked
+                (None, None) => $crate::hooks!(@zero [$($ret)?]),
+            }
+        }
+
+        // not every detour calls `orig()`
+        #[allow(non_snake_case, dead_code)]
+        pub mod $target {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub type Type = unsafe extern "C" fn($($ty),*) $(-> $ret)?;
+
+            // the registry owns it, this is just so detours don't have to look it up
+            pub(super) static HOOK: ::std::sync::OnceLock<::std::sync::Arc<$crate::memory::Hook<Type>>> =
+                ::std::sync::OnceLock::new();
+
+            pub fn hook() -> &'static $crate::memory::Hook<Type> {
+                HOOK.get().expect(concat!(stringify!($target), " isn't hooked"))
+            }
+
+            ::std::thread_local! {
+                // whether the detour got as far as calling orig, so a panic after doesn't call it twice
+                pub(super) static ORIG_CALLED: ::std::cell::Cell<bool> = const { ::std::cell::Cell::new(false) };
+            }
+
+            pub(super) static STATS: $crate::trace::HookStats = $crate::trace::HookStats::new();
+
+            pub fn orig() -> Type {
+                ORIG_CALLED.set(true);
+                hook().orig
+            }
+
+            pub(super) fn install(
+                registry: &$crate::hooks::HookRegistry,
+                addrs: &::std::collections::BTreeMap<
+                    ::std::string::String,
+                    ::std::result::Result<*mut u8, $crate::sigtable::ResolveError>,
+                >,
+            ) -> bool {
+                let Some(Ok(addr)) = addrs.get(stringify!($target)) else {
+                    return false;
+                };
+                let created = unsafe {
+                    $crate::memory::hook_function_from_addr::<Type>(
+                        *addr as *mut ::std::ffi::c_void,
+                        super::$detour as Type,
+                    )
+                };
+                let Some(created) = created else {
+                    return false;
+                };
+                let Ok(created) = registry.insert(stringify!($target), created) else {
+                    return false;
+                };
+                // installed into another registry already
+                if HOOK.set(created).is_err() {
+                    let err = $crate::hooks::HookError::AlreadyCreated;
+                    $crate::hooks::install_failed(registry, stringify!($target), err);
+                    return false;
+                }
+                // `HOOK` keeps a removed hook if this fails, but nothing calls a detour that was
+                // never enabled
+                if let Err(err) = hook().enable() {
+                    $crate::hooks::install_failed(registry, stringify!($target), err);
+                    return false;
+                }
+                $crate::trace::register(stringify!($target), &STATS);
+                true
+            }
+        }
+
+        $crate::hooks!(@munch $vis $install $functions [$($hook)* $target] $($rest)*);
+    };
+
+    (@munch $vis:vis $install:ident [$($function:ident)*] $hooks:tt
+        #[function]
+        type $target:ident = $fn_type:ty;
+        $($rest:tt)*
+    ) => {
+        #[allow(non_snake_case)]
+        pub mod $target {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub type Type = $fn_type;
+
+            static FUNCTION: ::std::sync::OnceLock<Type> = ::std::sync::OnceLock::new();
+
+            pub fn get() -> Type {
+                *FUNCTION.get().expect(concat!(stringify!($target), " isn't resolved"))
+            }
+
+            pub(super) fn install(
+                _: &$crate::hooks::HookRegistry,
+                addrs: &::std::collections::BTreeMap<
+                    ::std::string::String,
+                    ::std::result::Result<*mut u8, $crate::sigtable::ResolveError>,
+                >,
+            ) -> bool {
+                let Some(Ok(addr)) = addrs.get(stringify!($target)) else {
+                    return false;
+                };
+                FUNCTION.set(unsafe { ::std::mem::transmute::<*mut u8, Type>(*addr) }).is_ok()
+            }
+        }
+
+        $crate::hooks!(@munch $vis $install [$($function)* $target] $hooks $($rest)*);
+    };
+
+    ($vis:vis fn $install:ident; $($rest:tt)*) => {
+        $crate::hooks!(@munch $vis $install [] [] $($rest)*);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{hook_function_with_backend, Hook};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+
+    type Game = unsafe extern "C" fn(u32) -> u32;
+
+    // distinct bodies so they don't get folded into one function
+    unsafe extern "C" fn target(x: u32) -> u32 {
+        std::hint::black_box(x).wrapping_add(1)
+    }
+
+    unsafe extern "C" fn detour(x: u32) -> u32 {
+        std::hint::black_box(x).wrapping_mul(10)
+    }
+
+    fn ptr(f: Game) -> *mut c_void {
+        f as *mut c_void
+    }
+
+    // what the game would run if it called `target` right now
+    fn call(backend: &MockBackend, target: Game, x: u32) -> u32 {
+        let f: Game = unsafe { std::mem::transmute(backend.effective(ptr(target))) };
+        unsafe { f(x) }
+    }
+
+    #[test]
+    fn mock_tracks_state() {
+        let backend = MockBackend::new();
+        let (t, d) = (ptr(target), ptr(detour));
+        unsafe {
+            assert_eq!(backend.enable(t), Err(HookError::NotCreated));
+            assert_eq!(backend.create(t, d), Ok(t));
+            assert_eq!(backend.create(t, d), Err(HookError::AlreadyCreated));
+            assert!(backend.is_created(t) && !backend.is_enabled(t));
+            assert_eq!(backend.detour(t), Some(d));
+            assert_eq!(backend.disable(t), Err(HookError::NotEnabled));
+            assert_eq!(backend.enable(t), Ok(()));
+            assert_eq!(backend.enable(t), Err(HookError::AlreadyEnabled));
+            assert!(backend.is_enabled(t));
+            assert_eq!(backend.disable(t), Ok(()));
+            assert_eq!(backend.remove(t), Ok(()));
+            assert_eq!(backend.remove(t), Err(HookError::NotCreated));
+        }
+        assert!(!backend.is_created(t));
+        let (t, d) = (t as usize, d as usize);
+        assert_eq!(
+            backend.calls(),
+            [
+                MockCall::Enable(t),
+                MockCall::Create { target: t, detour: d },
+                MockCall::Create { target: t, detour: d },
+                MockCall::Disable(t),
+                MockCall::Enable(t),
+                MockCall::Enable(t),
+                MockCall::Disable(t),
+                MockCall::Remove(t),
+                MockCall::Remove(t),
+            ]
+        );
+        backend.clear_calls();
+        assert!(backend.calls().is_empty());
+    }
+
+    unsafe extern "C" fn routed_target(x: u32) -> u32 {
+        std::hint::black_box(x).wrapping_add(2)
+    }
+
+    static ROUTED: OnceLock<Hook<Game>> = OnceLock::new();
+
+    unsafe extern "C" fn routed_detour(x: u32) -> u32 {
+        100 + unsafe { (ROUTED.get().unwrap().orig)(x) }
+    }
+
+    #[test]
+    fn calls_go_through_the_detour_while_enabled() {
+        static BACKEND: MockBackend = MockBackend::new();
+        let hook = unsafe { hook_function_with_backend::<Game>(&BACKEND, ptr(routed_target), routed_detour) };
+        let hook = ROUTED.get_or_init(|| hook.unwrap());
+        assert_eq!(call(&BACKEND, routed_target, 1), 3);
+        hook.enable().unwrap();
+        assert_eq!(call(&BACKEND, routed_target, 1), 103);
+        // orig never goes through the detour
+        assert_eq!(unsafe { (hook.orig)(1) }, 3);
+        {
+            let _guard = hook.suspend();
+            let _nested = hook.suspend();
+            assert_eq!(call(&BACKEND, routed_target, 1), 3);
+        }
+        assert_eq!(call(&BACKEND, routed_target, 1), 103);
+        hook.disable().unwrap();
+        assert_eq!(call(&BACKEND, routed_target, 1), 3);
+    }
+
+    #[test]
+    fn registry_owns_hooks() {
+        static BACKEND: MockBackend = MockBackend::new();
+        unsafe extern "C" fn registry_target(x: u32) -> u32 {
+            std::hint::black_box(x).wrapping_add(3)
+        }
+        let registry = HookRegistry::new();
+        let hook = unsafe { hook_function_with_backend::<Game>(&BACKEND, ptr(registry_target), detour) }.unwrap();
+        let hook = registry.insert("target", hook).unwrap();
+        assert!(registry.get::<Hook<Game>>("target").is_some_and(|other| Arc::ptr_eq(&hook, &other)));
+        assert!(registry.enable_all().is_empty());
+        assert!(registry.enable_all().is_empty(), "already enabled isn't a failure");
+        assert!(BACKEND.is_enabled(ptr(registry_target)));
+        assert_eq!(registry.remove("target"), Ok(()));
+        assert_eq!(registry.remove("target"), Err(HookError::NotCreated));
+        assert!(!BACKEND.is_created(ptr(registry_target)));
+    }
+
+    // everything below goes through `hooks!`, which always uses `default_backend`
+    #[cfg(not(windows))]
+    mod generated {
+        use super::*;
+
+
+        static ORIG_CALLS: AtomicUsize = AtomicUsize::new(0);
+        static SENT: AtomicUsize = AtomicUsize::new(0);
+
+        unsafe extern "C" fn game_add(x: u32) -> u32 {
+            std::hint::black_box(x).wrapping_add(4)
+        }
+
+        unsafe extern "C" fn game_double(x: u32) -> u32 {
+            std::hint::black_box(x).wrapping_mul(2)
+        }
+
+        unsafe extern "C" fn game_unit(_: u32) {
+            ORIG_CALLS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        unsafe extern "C" fn game_sent(x: u32) -> u32 {
+            SENT.fetch_add(1, Ordering::Relaxed);
+            x + 1
+        }
+
+        unsafe extern "C" fn game_ptr(x: u32) -> *mut u8 {
+            std::hint::black_box(x as usize + 5) as *mut u8
+        }
+
+        unsafe extern "C" fn game_disabled(x: u32) -> u32 {
+            std::hint::black_box(x).wrapping_sub(6)
+        }
+
+        crate::hooks! {
+            fn install;
+
+            #[hook(Add)]
+            unsafe extern "C" fn add(x: u32) -> u32 {
+                // `Double` is a function, it's installed first even though it's declared last
+                unsafe { Add::orig()(x) + Double::get()(x) }
+            }
+
+            #[hook(Unit)]
+            unsafe extern "C" fn unit(x: u32) {
+                unsafe { Unit::orig()(x) };
+                if x == 0 {
+                    panic!("after orig");
+                }
+            }
+
+            #[hook(Sent)]
+            unsafe extern "C" fn sent(x: u32) -> u32 {
+                let result = unsafe { Sent::orig()(x) };
+                if x == 0 {
+                    panic!("after orig");
+                }
+                result
+            }
+
+            #[hook(Ptr)]
+            unsafe extern "C" fn ptr_detour(x: u32) -> *mut u8 {
+                if x == 0 {
+                    panic!("before orig");
+                }
+                unsafe { Ptr::orig()(x) }
+            }
+
+            #[hook(Disabled, disable_on_panic)]
+            unsafe extern "C" fn disabled(x: u32) -> u32 {
+                if x == 0 {
+                    panic!("every frame");
+                }
+                x
+            }
+
+            #[hook(Missing)]
+            unsafe extern "C" fn missing(x: u32) -> u32 {
+                unsafe { Missing::orig()(x) + 1 }
+            }
+
+            #[function]
+            type Double = unsafe extern "C" fn(u32) -> u32;
+        }
+
+        static REGISTRY: HookRegistry = HookRegistry::new();
+
+        // statics `hooks!` made are set once, so every test shares one install
+        fn installed() {
+            static INSTALL: OnceLock<Result<(), Vec<&'static str>>> = OnceLock::new();
+            let result = INSTALL.get_or_init(|| {
+                let addrs = [
+                    ("Add", game_add as *mut u8),
+                    ("Unit", game_unit as *mut u8),
+                    ("Sent", game_sent as *mut u8),
+                    ("Ptr", game_ptr as *mut u8),
+                    ("Disabled", game_disabled as *mut u8),
+                    ("Double", game_double as *mut u8),
+                ];
+                install(&REGISTRY, &addrs.into_iter().map(|(name, addr)| (name.to_string(), Ok(addr))).collect())
+            });
+            assert_eq!(*result, Err(vec!["Missing"]));
+        }
+
+        fn call<T: Copy>(target: T) -> T {
+            let target = unsafe { std::mem::transmute_copy::<T, *mut c_void>(&target) };
+            unsafe { std::mem::transmute_copy(&MOCK_BACKEND.effective(target)) }
+        }
+
+        #[test]
+        fn installs_and_enables() {
+            installed();
+            assert_eq!(REGISTRY.names(), ["Add", "Disabled", "Ptr", "Sent", "Unit"]);
+            assert!(MOCK_BACKEND.is_enabled(game_add as *mut c_void));
+            assert_eq!(Add::hook().target as usize, game_add as *const () as usize);
+            assert_eq!(Double::get() as usize, game_double as *const () as usize);
+            assert_eq!(unsafe { call::<Game>(game_add)(1) }, 5 + 2);
+        }
+
+        #[test]
+        fn panic_after_orig_doesnt_call_it_again() {
+            installed();
+            let before = ORIG_CALLS.load(Ordering::Relaxed);
+            unsafe { call::<unsafe extern "C" fn(u32)>(game_unit)(0) };
+            assert_eq!(ORIG_CALLS.load(Ordering::Relaxed), before + 1);
+            assert!(MOCK_BACKEND.is_enabled(game_unit as *mut c_void));
+        }
+
+        #[test]
+        fn panic_after_orig_returns_zero() {
+            installed();
+            let f = call::<Game>(game_sent);
+            let before = SENT.load(Ordering::Relaxed);
+            assert_eq!(unsafe { f(0) }, 0);
+            assert_eq!(SENT.load(Ordering::Relaxed), before + 1);
+            assert_eq!(unsafe { f(2) }, 3);
+            assert_eq!(SENT.load(Ordering::Relaxed), before + 2);
+        }
+
+        #[test]
+        fn panic_falls_back_to_orig() {
+            installed();
+            let f = call::<unsafe extern "C" fn(u32) -> *mut u8>(game_ptr);
+            assert_eq!(unsafe { f(0) } as usize, 5);
+            assert_eq!(unsafe { f(1) } as usize, 6);
+            assert!(MOCK_BACKEND.is_enabled(game_ptr as *mut c_void));
+        }
+
+        #[test]
+        fn disable_on_panic() {
+            installed();
+            assert_eq!(unsafe { call::<Game>(game_disabled)(7) }, 7);
+            assert_eq!(unsafe { call::<Game>(game_disabled)(0) }, 0u32.wrapping_sub(6));
+            assert!(!MOCK_BACKEND.is_enabled(game_disabled as *mut c_void));
+            assert_eq!(unsafe { call::<Game>(game_disabled)(7) }, 1);
+        }
+
+        #[test]
+        fn panic_before_install_returns_zero() {
+            installed();
+            // `orig()` panics since there's no hook, the fallback mustn't
+            assert!(Missing::HOOK.get().is_none());
+            assert_eq!(unsafe { missing(1) }, 0);
+        }
+    }
+}
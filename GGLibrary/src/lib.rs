@@ -1,5 +1,23 @@
+#![allow(clippy::missing_safety_doc)]
+
+pub mod cache;
 pub mod cxxstd;
+pub mod global;
+pub mod hooks;
 pub mod memory;
 pub mod output;
+pub mod panic;
+pub mod pointer;
+pub mod pe;
 pub mod red;
+pub mod reflect;
+pub mod region;
+pub mod ring;
+pub mod sigmaker;
+pub mod signature;
+pub mod snapshot;
+pub mod sigtable;
+pub mod trace;
+pub mod vmt;
+#[cfg(windows)]
 pub mod ue4ss;
@@ -1,17 +1,46 @@
-use minhook::MinHook;
-use std::cell::LazyCell;
+use crate::hooks::{default_backend, HookBackend, HookError, RegisteredHook};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::ffi::c_void;
-use std::fmt::format;
-use std::i8::MAX;
 use std::mem::ManuallyDrop;
-use std::ops::{Add, Deref};
-use std::sync::LazyLock;
-use widestring::{U16CString, U16String};
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::sync::{Arc, Mutex};
 
-use crate::output::budget_log;
+#[cfg(windows)]
+use crate::cache::{AddressCache, CacheKey};
+#[cfg(windows)]
+use log::{debug, info};
+#[cfg(windows)]
+use crate::pe::PeHeaders;
+#[cfg(windows)]
+use crate::pointer::{PathError, PathParseError, PointerPath};
+#[cfg(windows)]
+use crate::region::default_regions;
+use crate::signature::Signature;
+#[cfg(windows)]
+use crate::signature::ScanError;
+#[cfg(windows)]
+use crate::sigtable::{ResolveError, SignatureTable};
+#[cfg(windows)]
+use std::collections::BTreeMap;
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::sync::LazyLock;
+#[cfg(windows)]
+use widestring::U16CString;
+#[cfg(windows)]
 use winapi::um::libloaderapi::GetModuleHandleW;
-use winapi::um::processthreadsapi::GetCurrentProcess;
+#[cfg(windows)]
+use winapi::um::memoryapi::VirtualProtect;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{FlushInstructionCache, GetCurrentProcess};
+#[cfg(windows)]
 use winapi::um::psapi::{GetModuleInformation, MODULEINFO};
+#[cfg(windows)]
+use winapi::um::winnt::PAGE_EXECUTE_READWRITE;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -28,6 +57,62 @@ impl<T> Deref for ThreadSafePtr<T> {
     }
 }
 
+/// One step from an instruction to the address it references
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operand {
+    /// signed disp32 `offset` bytes into an instruction `len` bytes long,
+    /// e.g. `Rip { offset: 3, len: 7 }` for `48 8B 1D disp32`
+    Rip { offset: usize, len: usize },
+    /// target of an `E8` call, `E9` jmp or `EB` short jmp, following any jmp thunks it lands on
+    Branch,
+    Add(isize),
+}
+
+// jmp thunks never chain this deep, anything longer is garbage or a loop
+const MAX_JUMP_CHAIN: usize = 16;
+
+/// Address referenced by the rip-relative operand of the instruction at `inst`, all addresses
+/// are offsets into `image`. `None` if it's outside `image`.
+pub fn read_rip(image: &[u8], inst: usize, offset: usize, len: usize) -> Option<usize> {
+    let disp = image.get(inst.checked_add(offset)?..)?.get(..4)?;
+    let disp = i32::from_le_bytes(disp.try_into().unwrap());
+    inst.checked_add(len)?.checked_add_signed(disp as isize).filter(|&addr| addr < image.len())
+}
+
+/// Target of the `E8`/`E9` rel32 or `EB` rel8 at `inst`
+pub fn read_branch(image: &[u8], inst: usize) -> Option<usize> {
+    match image.get(inst)? {
+        0xe8 | 0xe9 => read_rip(image, inst, 1, 5),
+        0xeb => {
+            let disp = *image.get(inst.checked_add(1)?)? as i8;
+            inst.checked_add(2)?.checked_add_signed(disp as isize).filter(|&addr| addr < image.len())
+        }
+        _ => None,
+    }
+}
+
+/// Follows `E9`/`EB` jmps starting at `addr` until it reaches something that isn't one
+pub fn follow_jumps(image: &[u8], mut addr: usize) -> Option<usize> {
+    for _ in 0..MAX_JUMP_CHAIN {
+        if !matches!(image.get(addr), Some(0xe9 | 0xeb)) {
+            return Some(addr);
+        }
+        addr = read_branch(image, addr)?;
+    }
+    None
+}
+
+/// Applies `operands` in order starting from the instruction at `inst`
+pub fn resolve_in(image: &[u8], inst: usize, operands: &[Operand]) -> Option<usize> {
+    operands.iter().try_fold(inst, |addr, operand| match *operand {
+        Operand::Rip { offset, len } => read_rip(image, addr, offset, len),
+        Operand::Branch => follow_jumps(image, read_branch(image, addr)?),
+        Operand::Add(n) => addr.checked_add_signed(n),
+    })
+}
+
+#[cfg(windows)]
 static MODULE_BOUNDS: LazyLock<(ThreadSafePtr<u8>, ThreadSafePtr<u8>)> = LazyLock::new(|| {
     let module_name: U16CString = U16CString::from_str("GGST-Win64-Shipping.exe").unwrap();
     let handle = unsafe { GetModuleHandleW(module_name.as_ptr()) };
@@ -55,86 +140,717 @@ static MODULE_BOUNDS: LazyLock<(ThreadSafePtr<u8>, ThreadSafePtr<u8>)> = LazyLoc
     }
 });
 
-pub fn signature_scan(pattern: &str) -> Option<*mut u8> {
-    signature_scan_from_addr(pattern, *MODULE_BOUNDS.0)
+/// The mapped game module, offsets into it are RVAs
+#[cfg(windows)]
+pub fn module_image() -> &'static [u8] {
+    let (start, end) = *MODULE_BOUNDS;
+    unsafe { std::slice::from_raw_parts(*start, end.offset_from(*start) as usize) }
+}
+
+#[cfg(windows)]
+pub fn signature_scan(sig: &Signature) -> Option<*mut u8> {
+    signature_scan_from_addr(sig, *MODULE_BOUNDS.0)
+}
+
+/// Applies `operands` to the instruction at `inst`, which has to be inside the game module
+#[cfg(windows)]
+pub fn resolve_operands(inst: *mut u8, operands: &[Operand]) -> Option<*mut u8> {
+    let base = *MODULE_BOUNDS.0;
+    let offset = (inst as usize).checked_sub(base as usize)?;
+    resolve_in(module_image(), offset, operands).map(|offset| base.wrapping_add(offset))
 }
 
-pub fn signature_scan_from_addr(pattern: &str, start: *mut u8) -> Option<*mut u8> {
-    let (_, end) = *MODULE_BOUNDS;
-    let end = *end;
-    let mut i = start;
-    let mut j = 0;
-    let mut pattern_start = start;
-    let pattern = pattern.split(" ").collect::<Vec<&str>>();
-    while i < end {
-        if j == pattern.len() {
-            return Some(unsafe { pattern_start.add(1) });
+/// Scans for the unique match of `sig` and applies `operands` to it, e.g. the global a `mov` reads
+#[cfg(windows)]
+pub fn signature_scan_resolve(sig: &Signature, operands: &[Operand]) -> Option<*mut u8> {
+    let inst = signature_scan_unique(sig)
+        .inspect_err(|err| warn!("signature scan failed for: {} ({})", sig, err))
+        .ok()?;
+    resolve_operands(inst, operands)
+}
+
+#[cfg(windows)]
+fn address_cache_path() -> PathBuf {
+    crate::ue4ss::MOD_DIR.join("signature_cache.toml")
+}
+
+#[cfg(windows)]
+static MODULE_HEADERS: LazyLock<Option<PeHeaders>> = LazyLock::new(|| {
+    PeHeaders::parse(module_image())
+        .inspect_err(|err| error!("failed to read game headers: {}", err))
+        .ok()
+});
+
+// match offsets from the last run, thrown away when the game updates
+#[cfg(windows)]
+static ADDRESS_CACHE: LazyLock<Mutex<AddressCache>> = LazyLock::new(|| {
+    let key = MODULE_HEADERS.as_ref().map(CacheKey::of).unwrap_or_default();
+    Mutex::new(AddressCache::load(address_cache_path(), key))
+});
+
+// `.text` of the game, where every signature is scanned
+#[cfg(windows)]
+fn module_code_range() -> Range<usize> {
+    let len = module_image().len();
+    MODULE_HEADERS.as_ref().map_or(0..len, |headers| headers.code_range(len))
+}
+
+#[cfg(windows)]
+fn save_address_cache(cache: &mut AddressCache) {
+    if cache.is_dirty()
+        && let Err(err) = cache.save(address_cache_path())
+    {
+        warn!("failed to save signature cache: {}", err);
+    }
+}
+
+/// Resolves `name` from a mod's signature table against the game module. Matches are cached per
+/// game build next to the mod so later launches skip the scan.
+#[cfg(windows)]
+pub fn resolve_signature(table: &SignatureTable, name: &str) -> Option<*mut u8> {
+    let mut cache = ADDRESS_CACHE.lock().unwrap();
+    let result = table.resolve_cached(module_image(), name, &mut cache);
+    save_address_cache(&mut cache);
+    match result {
+        Ok(rva) => {
+            info!("{} at {:#x}", name, rva);
+            Some(MODULE_BOUNDS.0.wrapping_add(rva))
         }
-        if pattern[j] == "??"
-            || pattern[j] == "?"
-            || u8::from_str_radix(pattern[j], 16).unwrap() == unsafe { *i }
-        {
-            j += 1;
-        } else {
-            j = 0;
-            pattern_start = i;
+        Err(err) => {
+            warn!("failed to resolve {}: {}", name, err);
+            None
         }
-        unsafe { i = i.offset(1) };
     }
-    None
 }
 
+/// Resolves every entry of a mod's signature table with a single sweep of the game's `.text`,
+/// failures are logged and returned together rather than stopping at the first one
+#[cfg(windows)]
+pub fn resolve_signatures(table: &SignatureTable) -> BTreeMap<String, Result<*mut u8, ResolveError>> {
+    let mut cache = ADDRESS_CACHE.lock().unwrap();
+    let results = table.resolve_batch(module_image(), &mut cache);
+    save_address_cache(&mut cache);
+
+    results
+        .into_iter()
+        .map(|(name, result)| {
+            match &result {
+                Ok(rva) => info!("{} at {:#x}", name, rva),
+                Err(err) => warn!("failed to resolve {}: {}", name, err),
+            }
+            (name.to_string(), result.map(|rva| MODULE_BOUNDS.0.wrapping_add(rva)))
+        })
+        .collect()
+}
+
+/// Live address of the part of `path` before its first `->`, the global itself rather than
+/// what it points at
+#[cfg(windows)]
+pub fn resolve_path_static(path: &PointerPath, table: Option<&SignatureTable>) -> Result<*mut u8, PathError> {
+    let mut cache = ADDRESS_CACHE.lock().unwrap();
+    let result = path.resolve_static(module_image(), table, &mut cache);
+    save_address_cache(&mut cache);
+    result.map(|rva| MODULE_BOUNDS.0.wrapping_add(rva))
+}
+
+/// Resolves every hop of `path`, pointers are only followed through readable memory
+#[cfg(windows)]
+pub fn resolve_path(path: &PointerPath, table: Option<&SignatureTable>) -> Result<*mut u8, PathError> {
+    let addr = resolve_path_static(path, table)?;
+    path.follow(addr as usize, default_regions()).map(|addr| addr as *mut u8)
+}
+
+/// `default` unless the mod's `paths.toml` has `name = "..."`, so a path can be fixed after a
+/// game update without rebuilding the mod
+#[cfg(windows)]
+pub fn pointer_path(name: &str, default: &str) -> Result<PointerPath, PathParseError> {
+    let overrides = std::fs::read_to_string(crate::ue4ss::MOD_DIR.join("paths.toml"))
+        .ok()
+        .and_then(|s| toml::from_str::<BTreeMap<String, String>>(&s).ok())
+        .unwrap_or_default();
+    match overrides.get(name) {
+        Some(path) => {
+            info!("{} overridden: {}", name, path);
+            path.parse()
+        }
+        None => default.parse(),
+    }
+}
+
+/// Every match in the game's `.text`
+#[cfg(windows)]
+pub fn signature_scan_all(sig: &Signature) -> Vec<*mut u8> {
+    let base = *MODULE_BOUNDS.0;
+    let matches = sig.scan_all_in(module_image(), module_code_range());
+    matches.into_iter().map(|offset| unsafe { base.add(offset) }).collect()
+}
+
+/// The only match in the game's `.text`, offsets in the error are RVAs
+#[cfg(windows)]
+pub fn signature_scan_unique(sig: &Signature) -> Result<*mut u8, ScanError> {
+    let base = *MODULE_BOUNDS.0;
+    sig.scan_unique_in(module_image(), module_code_range()).map(|offset| unsafe { base.add(offset) })
+}
+
+#[cfg(windows)]
+pub fn signature_scan_from_addr(sig: &Signature, start: *mut u8) -> Option<*mut u8> {
+    let base = *MODULE_BOUNDS.0;
+    let text = module_code_range();
+    let from = (start as usize).checked_sub(base as usize)?.max(text.start);
+    sig.scan_from(&module_image()[..text.end], from).map(|offset| unsafe { base.add(offset) })
+}
+
+/// A created hook. `orig` calls the original function, `target` is where the game calls it.
+/// Calling `orig` never goes through the detour, so there's no need to disable the hook just to
+/// get the original behaviour.
 pub struct Hook<T>
 where
     T: Copy,
 {
     pub target: T,
     pub orig: T,
+    pub detour: T,
+    backend: &'static dyn HookBackend,
+    // live suspend guards, and whether the first one disabled the hook
+    suspended: Mutex<(usize, bool)>,
 }
 
 impl<T: Copy> Hook<T> {
-    pub fn enable(&self) {
-        unsafe { MinHook::enable_hook(std::mem::transmute_copy(&ManuallyDrop::new(self.target))) };
-    } // crying and sobbing
-    pub fn disable(&self) {
-        unsafe { MinHook::disable_hook(std::mem::transmute_copy(&ManuallyDrop::new(self.target))) };
+    fn target_ptr(&self) -> *mut c_void {
+        unsafe { std::mem::transmute_copy(&ManuallyDrop::new(self.target)) } // crying and sobbing
+    }
+
+    pub fn enable(&self) -> Result<(), HookError> {
+        unsafe { self.backend.enable(self.target_ptr()) }
+    }
+
+    pub fn disable(&self) -> Result<(), HookError> {
+        unsafe { self.backend.disable(self.target_ptr()) }
+    }
+
+    /// Unpatches the target, `orig` must not be called afterwards
+    pub fn remove(self) -> Result<(), HookError> {
+        self.remove_ref()
+    }
+
+    fn remove_ref(&self) -> Result<(), HookError> {
+        unsafe { self.backend.remove(self.target_ptr()) }?;
+        release(hook_claim(self.target_ptr()));
+        Ok(())
+    }
+
+    /// Disables the hook until every guard from this and nested calls is dropped. A hook that was
+    /// already disabled stays that way.
+    pub fn suspend(&self) -> HookSuspendGuard<'_, T> {
+        let mut suspended = self.suspended.lock().unwrap();
+        if suspended.0 == 0 {
+            suspended.1 = self.disable().is_ok();
+        }
+        suspended.0 += 1;
+        HookSuspendGuard { hook: self }
     }
 }
 
-pub fn hook_function<T: Copy>(sig: &str, hook: T) -> Option<Hook<T>> {
-    let addr = signature_scan(sig);
-    if addr.is_none() {
-        budget_log(
-            ("signature scan failed for: ".to_owned() + std::any::type_name::<T>()).as_str(),
-        );
-        return None;
+impl<T: Copy + Send + Sync + 'static> RegisteredHook for Hook<T> {
+    fn enable(&self) -> Result<(), HookError> {
+        Hook::enable(self)
+    }
+
+    fn disable(&self) -> Result<(), HookError> {
+        Hook::disable(self)
     }
-    hook_function_from_addr(addr.unwrap() as *mut c_void, hook)
+
+    fn remove(&self) -> Result<(), HookError> {
+        self.remove_ref()
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Keeps a hook disabled while alive, see `Hook::suspend`
+#[must_use]
+pub struct HookSuspendGuard<'a, T: Copy> {
+    hook: &'a Hook<T>,
 }
 
-pub fn hook_function_from_addr<T: Copy>(addr: *mut c_void, hook: T) -> Option<Hook<T>> {
+impl<T: Copy> Drop for HookSuspendGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut suspended = self.hook.suspended.lock().unwrap();
+        suspended.0 -= 1;
+        if suspended.0 == 0 && suspended.1 {
+            let _ = self.hook.enable();
+        }
+    }
+}
+
+/// How `hook_function_with_mode` treats a signature with several matches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScanMode {
+    /// take the first match
+    First,
+    /// fail unless there is exactly one match
+    #[default]
+    Unique,
+}
+
+#[cfg(windows)]
+pub fn hook_function<T: Copy>(sig: &Signature, hook: T) -> Option<Hook<T>> {
+    hook_function_with_mode(sig, hook, ScanMode::default())
+}
+
+#[cfg(windows)]
+pub fn hook_function_with_mode<T: Copy>(sig: &Signature, hook: T, mode: ScanMode) -> Option<Hook<T>> {
+    let name = std::any::type_name::<T>();
+    let matches = sig.scan_all_in(module_image(), module_code_range());
+    debug!("{} match(es) for: {}", matches.len(), name);
+    let offset = match mode {
+        ScanMode::First => matches.first().copied().ok_or(ScanError::NotFound),
+        ScanMode::Unique => ScanError::check_unique(matches),
+    };
+    match offset {
+        Ok(offset) => {
+            let addr = unsafe { MODULE_BOUNDS.0.add(offset) };
+            unsafe { hook_function_from_addr(addr as *mut c_void, hook) }
+        }
+        Err(err) => {
+            warn!("signature scan failed for: {} ({})", name, err);
+            None
+        }
+    }
+}
+
+/// Hooks `name` from a mod's signature table
+#[cfg(windows)]
+pub fn hook_signature<T: Copy>(table: &SignatureTable, name: &str, hook: T) -> Option<Hook<T>> {
+    unsafe { hook_function_from_addr(resolve_signature(table, name)? as *mut c_void, hook) }
+}
+
+/// `addr` has to be the start of a function
+pub unsafe fn hook_function_from_addr<T: Copy>(addr: *mut c_void, hook: T) -> Option<Hook<T>> {
+    unsafe { hook_function_with_backend(default_backend(), addr, hook) }
+}
+
+pub unsafe fn hook_function_with_backend<T: Copy>(
+    backend: &'static dyn HookBackend,
+    addr: *mut c_void,
+    hook: T,
+) -> Option<Hook<T>> {
+    match claim(hook_claim(addr), Claim::Hook) {
+        Ok(()) => {}
+        Err(Claim::Hook) => {
+            warn!("can't hook {:p}, it's already hooked", addr);
+            return None;
+        }
+        Err(Claim::Patch) => {
+            warn!("can't hook {:p}, it overlaps a patch", addr);
+            return None;
+        }
+    }
+    // MinHook chains onto it fine, but the other hook owns these bytes now
+    if unsafe { starts_with_jump(addr.cast()) } {
+        warn!("{:p} starts with a jmp, another mod or UE4SS may have hooked it already", addr);
+    }
     let res = unsafe {
-        MinHook::create_hook(
+        backend.create(
             addr,
             std::mem::transmute_copy(&ManuallyDrop::new(hook)),
         )
     };
-    if res.is_err() {
-        budget_log(("create hook failed for: ".to_owned() + std::any::type_name::<T>()).as_str());
-        budget_log(format!("{:?}", res.unwrap_err()).as_str());
-        return None;
-    }
-
-    let orig = res.unwrap();
+    let orig = match res {
+        Ok(orig) => orig,
+        Err(err) => {
+            release(hook_claim(addr));
+            error!("create hook failed for: {} ({})", std::any::type_name::<T>(), err);
+            return None;
+        }
+    };
     unsafe {
         Some(Hook {
             target: std::mem::transmute_copy(&ManuallyDrop::new(addr)),
             orig: std::mem::transmute_copy(&ManuallyDrop::new(orig)),
+            detour: hook,
+            backend,
+            suspended: Mutex::new((0, false)),
         })
     }
 }
 
-pub fn print_memory(ptr: *const u8, len: usize) -> String {
+/// Writes `value` to `dst` even if its page is read-only, e.g. a vtable in `.rdata`
+pub unsafe fn write_protected<T: Copy>(dst: *mut T, value: T) -> std::io::Result<()> {
+    let bytes = unsafe { std::slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+    unsafe { write_protected_bytes(dst.cast(), bytes) }
+}
+
+/// Copies `bytes` to `dst` even if its page is read-only or code
+pub unsafe fn write_protected_bytes(dst: *mut u8, bytes: &[u8]) -> std::io::Result<()> {
+    #[cfg(windows)]
+    unsafe {
+        let mut old = 0;
+        if VirtualProtect(dst.cast(), bytes.len(), PAGE_EXECUTE_READWRITE, &mut old) == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+        VirtualProtect(dst.cast(), bytes.len(), old, &mut old);
+        FlushInstructionCache(GetCurrentProcess(), dst.cast(), bytes.len());
+    }
+    #[cfg(not(windows))]
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+    }
+    Ok(())
+}
+
+// MinHook overwrites the first instructions of a hooked function and copies them into the
+// trampoline, 16 bytes covers the jump plus whatever instruction it cuts into
+const HOOK_CLAIM_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Claim {
+    Hook,
+    Patch,
+}
+
+// address ranges this DLL already overwrote, hooked function starts and enabled patches. Every
+// mod links its own gglibrary so other mods' (and UE4SS's) hooks aren't in here.
+static CLAIMED: Mutex<Vec<(Range<usize>, Claim)>> = Mutex::new(Vec::new());
+
+fn hook_claim(target: *mut c_void) -> Range<usize> {
+    target as usize..target as usize + HOOK_CLAIM_LEN
+}
+
+// `Err` is whatever already has some of `range`
+fn claim(range: Range<usize>, kind: Claim) -> Result<(), Claim> {
+    let mut claimed = CLAIMED.lock().unwrap();
+    if let Some(other) = overlap(&claimed, &range) {
+        return Err(other);
+    }
+    claimed.push((range, kind));
+    Ok(())
+}
+
+fn overlap(claimed: &[(Range<usize>, Claim)], range: &Range<usize>) -> Option<Claim> {
+    claimed.iter().find(|(other, _)| other.start < range.end && range.start < other.end).map(|(_, kind)| *kind)
+}
+
+// the bytes at `addr` aren't what a patch expects, says who changed them
+unsafe fn mismatch(addr: *mut u8, found: &[u8]) -> PatchError {
+    let range = addr as usize..addr as usize + found.len();
+    if overlap(&CLAIMED.lock().unwrap(), &range).is_some() {
+        PatchError::Overlaps
+    } else if found.len() >= 2 && unsafe { starts_with_jump(addr) } {
+        PatchError::ForeignHook
+    } else {
+        PatchError::Unexpected { found: found.to_vec() }
+    }
+}
+
+fn release(range: Range<usize>) {
+    let mut claimed = CLAIMED.lock().unwrap();
+    if let Some(i) = claimed.iter().position(|(other, _)| *other == range) {
+        claimed.swap_remove(i);
+    }
+}
+
+// `E9 rel32` or `FF 25 [rip+x]`, what MinHook and most other hooking libraries write over a
+// function's first bytes. A few game functions are jmp thunks on their own, so this is a hint.
+unsafe fn starts_with_jump(addr: *const u8) -> bool {
+    let bytes = unsafe { std::slice::from_raw_parts(addr, 2) };
+    bytes[0] == 0xe9 || bytes == [0xff, 0x25]
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    LengthMismatch { expected: usize, replacement: usize },
+    /// the bytes at the address aren't what the patch was written against
+    Unexpected { found: Vec<u8> },
+    /// a hook or another patch of this mod already owns some of these bytes
+    Overlaps,
+    /// starts with a jmp it wasn't written against, something else hooked the function
+    ForeignHook,
+    Protect(std::io::Error),
+}
+
+impl From<std::io::Error> for PatchError {
+    fn from(err: std::io::Error) -> PatchError {
+        PatchError::Protect(err)
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::LengthMismatch { expected, replacement } => {
+                write!(f, "expected {} bytes but replacement has {}", expected, replacement)
+            }
+            PatchError::Unexpected { found } => write!(f, "unexpected bytes {}", hex::encode(found)),
+            PatchError::Overlaps => write!(f, "overlaps a hook or another patch"),
+            PatchError::ForeignHook => write!(f, "already hooked by another mod or UE4SS"),
+            PatchError::Protect(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// An inline patch, e.g. NOPing a branch or changing the immediate of a compare. Created
+/// disabled, the original bytes are put back on `disable` and on drop.
+///
+/// Overlaps are only checked against this mod's own hooks and patches, every mod links its own
+/// gglibrary. A patch starting on a jmp that `expected` doesn't have is refused as someone else's
+/// hook, but one a few bytes into a function another mod or UE4SS hooked isn't caught: those
+/// bytes got moved to a trampoline and patching them does nothing until that hook is removed.
+///
+/// ```ignore
+/// // 83 fa 4f 76 -> 83 fa 63 76, cmp edx, 0x4f becomes cmp edx, 0x63
+/// let expected = Signature::from_ida("83 fa ? 76")?;
+/// let patch = unsafe { Patch::new(addr, &expected, &Signature::from_ida("? ? 63 ?")?) }?;
+/// patch.enable()?;
+/// ```
+pub struct Patch {
+    addr: *mut u8,
+    original: Vec<u8>,
+    patched: Vec<u8>,
+    enabled: Mutex<bool>,
+}
+
+unsafe impl Send for Patch {}
+unsafe impl Sync for Patch {}
+
+impl Patch {
+    /// Checks the bytes at `addr` against `expected`. Wildcards in `replacement` keep whatever
+    /// byte (or nibble) is already there.
+    pub unsafe fn new(addr: *mut u8, expected: &Signature, replacement: &Signature) -> Result<Self, PatchError> {
+        if expected.len() != replacement.len() {
+            return Err(PatchError::LengthMismatch { expected: expected.len(), replacement: replacement.len() });
+        }
+        let original = unsafe { std::slice::from_raw_parts(addr, expected.len()) }.to_vec();
+        if !expected.matches(&original) {
+            return Err(unsafe { mismatch(addr, &original) });
+        }
+        let patched = original
+            .iter()
+            .zip(replacement.bytes().iter().zip(replacement.mask()))
+            .map(|(o, (b, m))| (o & !m) | b)
+            .collect();
+        Ok(Self { addr, original, patched, enabled: Mutex::new(false) })
+    }
+
+    pub fn addr(&self) -> *mut u8 {
+        self.addr
+    }
+
+    pub fn len(&self) -> usize {
+        self.original.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.original.is_empty()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.lock().unwrap()
+    }
+
+    fn range(&self) -> Range<usize> {
+        self.addr as usize..self.addr as usize + self.len()
+    }
+
+    pub fn enable(&self) -> Result<(), PatchError> {
+        let mut enabled = self.enabled.lock().unwrap();
+        if *enabled {
+            return Ok(());
+        }
+        // claimed first, our own hook having written here is an overlap and not someone else's
+        if claim(self.range(), Claim::Patch).is_err() {
+            return Err(PatchError::Overlaps);
+        }
+        // something else may have written here since `new`
+        let current = unsafe { std::slice::from_raw_parts(self.addr, self.len()) };
+        if current != self.original {
+            release(self.range());
+            return Err(unsafe { mismatch(self.addr, current) });
+        }
+        if let Err(err) = unsafe { write_protected_bytes(self.addr, &self.patched) } {
+            release(self.range());
+            return Err(err.into());
+        }
+        *enabled = true;
+        Ok(())
+    }
+
+    pub fn disable(&self) -> Result<(), PatchError> {
+        let mut enabled = self.enabled.lock().unwrap();
+        if !*enabled {
+            return Ok(());
+        }
+        unsafe { write_protected_bytes(self.addr, &self.original) }?;
+        release(self.range());
+        *enabled = false;
+        Ok(())
+    }
+}
+
+impl Drop for Patch {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+pub unsafe fn print_memory(ptr: *const u8, len: usize) -> String {
     let slice = unsafe { std::slice::from_raw_parts(ptr , len) };
     hex::encode(slice)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sig(s: &str) -> Signature {
+        Signature::from_ida(s).unwrap()
+    }
+
+    // `code` written at `at` into 0x100 bytes of int3
+    fn code_at(code: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut image = vec![0xcc; 0x100];
+        for (at, bytes) in code {
+            image[*at..at + bytes.len()].copy_from_slice(bytes);
+        }
+        image
+    }
+
+    #[test]
+    fn rip_operands_are_signed() {
+        // mov rbx, [rip - 0x40] at 0x80
+        let image = code_at(&[(0x80, &[0x48, 0x8b, 0x1d, 0xc0, 0xff, 0xff, 0xff])]);
+        assert_eq!(read_rip(&image, 0x80, 3, 7), Some(0x80 + 7 - 0x40));
+        assert_eq!(resolve_in(&image, 0x80, &[Operand::Rip { offset: 3, len: 7 }, Operand::Add(-8)]), Some(0x3f));
+        // a disp32 past the end, and ones pointing before and after the image
+        assert_eq!(read_rip(&image, 0xfd, 3, 7), None);
+        assert_eq!(read_rip(&[0x48, 0x8b, 0x1d, 0xf0, 0xff, 0xff, 0xff], 0, 3, 7), None);
+        assert_eq!(read_rip(&[0x48, 0x8b, 0x1d, 0x00, 0x00, 0x00, 0x00], 0, 3, 7), None);
+        assert_eq!(read_rip(&[0x48, 0x8b, 0x1d, 0xfa, 0xff, 0xff, 0xff], 0, 3, 7), Some(1));
+        assert_eq!(read_rip(&image, usize::MAX, 3, 7), None);
+    }
+
+    #[test]
+    fn branches_backwards() {
+        // call -0x45 at 0x80, jmp -0x10 at 0x90
+        let image = code_at(&[(0x80, &[0xe8, 0xbb, 0xff, 0xff, 0xff]), (0x90, &[0xe9, 0xf0, 0xff, 0xff, 0xff])]);
+        assert_eq!(read_branch(&image, 0x80), Some(0x40));
+        assert_eq!(read_branch(&image, 0x90), Some(0x85));
+        assert_eq!(read_branch(&image, 0x85), None);
+        // jmps out of the image
+        assert_eq!(read_branch(&[0xe9, 0, 0, 0, 0x80], 0), None);
+        assert_eq!(read_branch(&[0xe9, 0, 0, 0, 0], 0), None);
+        // cut off
+        assert_eq!(read_branch(&[0xe8, 0, 0], 0), None);
+    }
+
+    #[test]
+    fn short_jumps() {
+        // jmp +0x10 at 0x20, jmp -0x12 at 0x32
+        let image = code_at(&[(0x20, &[0xeb, 0x10]), (0x32, &[0xeb, 0xee])]);
+        assert_eq!(read_branch(&image, 0x20), Some(0x32));
+        assert_eq!(read_branch(&image, 0x32), Some(0x22));
+        assert_eq!(read_branch(&[0xeb], 0), None);
+        assert_eq!(read_branch(&[0xeb, 0x80], 0), None);
+    }
+
+    #[test]
+    fn follows_jump_chains() {
+        // call 0x40 -> jmp 0x60 -> jmp 0x10 -> jmp short 0x08 -> the function
+        let image = code_at(&[
+            (0x80, &[0xe8, 0xbb, 0xff, 0xff, 0xff]),
+            (0x40, &[0xe9, 0x1b, 0x00, 0x00, 0x00]),
+            (0x60, &[0xe9, 0xab, 0xff, 0xff, 0xff]),
+            (0x10, &[0xeb, 0xf6]),
+            (0x08, &[0x48, 0x89, 0x5c, 0x24, 0x08]),
+        ]);
+        assert_eq!(follow_jumps(&image, 0x40), Some(0x08));
+        assert_eq!(resolve_in(&image, 0x80, &[Operand::Branch]), Some(0x08));
+        assert_eq!(follow_jumps(&image, 0x08), Some(0x08));
+
+        // a jmp to itself never ends, and a chain that leaves the image fails
+        let looped = code_at(&[(0x10, &[0xeb, 0xfe])]);
+        assert_eq!(follow_jumps(&looped, 0x10), None);
+        let out = code_at(&[(0x10, &[0xe9, 0x00, 0x10, 0x00, 0x00])]);
+        assert_eq!(follow_jumps(&out, 0x10), None);
+        assert_eq!(resolve_in(&code_at(&[(0, &[0xe9, 0x0b, 0, 0, 0]), (0x10, &[0xe9, 0, 0, 0, 0x80])]), 0, &[Operand::Branch]), None);
+    }
+
+    unsafe extern "C" fn detour() {}
+
+    #[test]
+    fn patch_round_trip() {
+        let mut code = vec![0x83, 0xfa, 0x4f, 0x76, 0x10];
+        let patch = unsafe { Patch::new(code.as_mut_ptr(), &sig("83 fa ? 76"), &sig("? ? 63 ?")) }.unwrap();
+        assert!(!patch.is_enabled());
+        assert_eq!(code, [0x83, 0xfa, 0x4f, 0x76, 0x10]);
+        patch.enable().unwrap();
+        patch.enable().unwrap();
+        assert!(patch.is_enabled());
+        assert_eq!(code, [0x83, 0xfa, 0x63, 0x76, 0x10]);
+        patch.disable().unwrap();
+        assert_eq!(code, [0x83, 0xfa, 0x4f, 0x76, 0x10]);
+
+        // its bytes are free again, and dropping it puts them back
+        patch.enable().unwrap();
+        let other = unsafe { Patch::new(code.as_mut_ptr().add(3), &sig("76"), &sig("eb")) }.unwrap();
+        assert!(matches!(other.enable(), Err(PatchError::Overlaps)));
+        drop(patch);
+        assert_eq!(code, [0x83, 0xfa, 0x4f, 0x76, 0x10]);
+        other.enable().unwrap();
+        assert_eq!(code, [0x83, 0xfa, 0x4f, 0xeb, 0x10]);
+        assert!(matches!(
+            unsafe { Patch::new(code.as_mut_ptr(), &sig("83 fa"), &sig("90")) },
+            Err(PatchError::LengthMismatch { expected: 2, replacement: 1 })
+        ));
+    }
+
+    #[test]
+    fn patch_overlapping_our_hook() {
+        let mut code = vec![0x48, 0x89, 0x5c, 0x24, 0x08, 0x57, 0x48, 0x83, 0xec, 0x20, 0, 0, 0, 0, 0, 0, 0, 0];
+        let early = unsafe { Patch::new(code.as_mut_ptr(), &sig("48 89 5c 24 08"), &sig("c3 ? ? ? ?")) }.unwrap();
+        let hook =
+            unsafe { hook_function_from_addr::<unsafe extern "C" fn()>(code.as_mut_ptr().cast(), detour) }.unwrap();
+        // what MinHook leaves behind
+        code[..5].copy_from_slice(&[0xe9, 1, 2, 3, 4]);
+        assert!(matches!(early.enable(), Err(PatchError::Overlaps)));
+        assert!(matches!(
+            unsafe { Patch::new(code.as_mut_ptr(), &sig("48 89 5c 24 08"), &sig("c3 ? ? ? ?")) },
+            Err(PatchError::Overlaps)
+        ));
+        let inside = unsafe { Patch::new(code.as_mut_ptr().add(6), &sig("48 83 ec 20"), &sig("? ? ? 40")) }.unwrap();
+        assert!(matches!(inside.enable(), Err(PatchError::Overlaps)));
+        // a second hook is refused as a duplicate rather than a patch overlap
+        assert!(
+            unsafe { hook_function_from_addr::<unsafe extern "C" fn()>(code.as_mut_ptr().cast(), detour) }.is_none()
+        );
+        // removing it frees the bytes, once they're back
+        hook.remove().unwrap();
+        code[..5].copy_from_slice(&[0x48, 0x89, 0x5c, 0x24, 0x08]);
+        early.enable().unwrap();
+        assert_eq!(code[0], 0xc3);
+    }
+
+    #[test]
+    fn patch_over_a_foreign_hook() {
+        let mut code = vec![0xe9, 1, 2, 3, 4, 0x90];
+        assert!(matches!(
+            unsafe { Patch::new(code.as_mut_ptr(), &sig("48 89 5c 24 08"), &sig("c3 ? ? ? ?")) },
+            Err(PatchError::ForeignHook)
+        ));
+        let patch = unsafe { Patch::new(code.as_mut_ptr().add(5), &sig("90"), &sig("cc")) }.unwrap();
+        code[4..6].copy_from_slice(&[0x12, 0x34]);
+        match patch.enable() {
+            Err(PatchError::Unexpected { found }) => assert_eq!(found, [0x34]),
+            other => panic!("{:?}", other),
+        }
+        // nothing of it stays claimed
+        code[5] = 0x90;
+        patch.enable().unwrap();
+        assert_eq!(code[5], 0xcc);
+    }
+}
@@ -1,58 +1,407 @@
-// use std::mem;
-// use std::ops::Deref;
-// use crate::budget_log;
-// use crate::cxxstd::{CxxStringView, CxxUniquePtr, CxxVector};
-//
-// #[repr(C)]
-// pub struct DeviceVtable {
-//     pub has_optional_args: unsafe extern "C" fn(*const Device) -> bool,
-//     pub receive: unsafe extern "C" fn(*const Device, *const CxxStringView),
-//     pub receive_with_optional_arg: unsafe extern "C" fn(*const Device, *const CxxStringView, u32),
-// }
-//
-// #[repr(C)]
-// struct Device {
-//     vtable: *const DeviceVtable,
-// }
-//
-// #[link(name="UE4SS", kind="raw-dylib")]
-// unsafe extern "C" {
-//     // msvc name mangling is stupid
-//     #[link_name = "?get_default_devices_ref@DefaultTargets@Output@RC@@SAAEAV?$vector@V?$unique_p tr@VOutputDevice@Output@RC@@U?$default_delete@VOutputDevice@Output@RC@@@std@@@st d@@V?$allocator@V?$unique_ptr@VOutputDevice@Output@RC@@U?$default_delete@VOutput Device@Output@RC@@@std@@@std@@@2@@std@@XZ();"]
-//     pub fn get_default_devices_ref() -> *mut CxxVector<CxxUniquePtr<Device>>;
-// }
-//
-// pub fn log() {
-//     unsafe {
-//         for device in (*get_default_devices_ref()).into_iter() {
-//             budget_log(format!("optional {}", ((*device.vtable).has_optional_args)(device.ptr)).as_str());
-//         }
-//     }
-// }
-
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::Path;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+#[cfg(windows)]
+use crate::cxxstd::{CxxStringView, CxxUniquePtr, CxxVector};
+#[cfg(windows)]
+use widestring::U16String;
+#[cfg(windows)]
+use winapi::shared::minwindef::FARPROC;
+#[cfg(windows)]
+use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
+
+/// Somewhere log lines end up, `line` is already formatted and has no newline. `name` is the
+/// logger's, usually the mod's.
+pub trait Sink: Send + Sync {
+    fn write(&self, name: &str, record: &Record, line: &str);
+
+    fn flush(&self) {}
+}
+
+/// The `log` backend. Every mod links its own copy of gglibrary (and of `log`), so every mod gets
+/// its own logger:
+///
+/// ```ignore
+/// Logger::new("RandomCharaColor")
+///     .level(LevelFilter::Debug)
+///     .sink(FileSink::new(MOD_DIR.join("logs/RandomCharaColor.log")))
+///     .init()?;
+/// ```
+pub struct Logger {
+    name: String,
+    level: LevelFilter,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Logger {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), level: LevelFilter::Info, sinks: Vec::new() }
+    }
+
+    /// Can't go past what `log` was compiled with, trace is gone from release builds
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level.min(log::STATIC_MAX_LEVEL);
+        self
+    }
+
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Fails if this copy of `log` already has a logger
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} [{}] {}: {}",
+            timestamp(SystemTime::now()),
+            record.level(),
+            self.name,
+            record.target(),
+            record.args()
+        );
+        for sink in &self.sinks {
+            sink.write(&self.name, record, &line);
+        }
+    }
+
+    fn flush(&self) {
+        for sink in &self.sinks {
+            sink.flush();
+        }
+    }
+}
+
+/// `2025-06-01 18:03:12.345`, in UTC
+pub fn timestamp(time: SystemTime) -> String {
+    Timestamp(time).to_string()
+}
+
+/// `timestamp` without the allocation, for writing straight into something
+pub struct Timestamp(pub SystemTime);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0.duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64);
+        let (days, ms) = (ms / 86_400_000, ms % 86_400_000);
+        // days since 1970 to a date, from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            year,
+            month,
+            day,
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    len: u64,
+    max_len: u64,
+    keep: usize,
+}
+
+impl LogFile {
+    fn open(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().append(true).create(true).open(&self.path)?;
+            self.len = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    // `x.log` -> `x.1.log` -> `x.2.log` ..., the oldest past `keep` is deleted
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        let rotated = |i: usize| self.path.with_extension(format!("{}.log", i));
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated(self.keep));
+            for i in (1..self.keep).rev() {
+                let _ = std::fs::rename(rotated(i), rotated(i + 1));
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+        self.len = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        self.open()?;
+        if self.len > 0 && self.len + line.len() as u64 + 1 > self.max_len && self.rotate().is_err() {
+            // something has the file open, keep appending and try again after another `max_len`
+            self.open()?;
+            self.len = 0;
+        }
+        let file = self.open()?;
+        writeln!(file, "{}", line)?;
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Appends to a file, which gets rotated once it's over `max_len` bytes. Nothing gets truncated
+/// on launch, old runs are still there until they rotate out. A file that can't be rotated keeps
+/// growing instead.
+pub struct FileSink {
+    file: Mutex<LogFile>,
+}
+
+impl FileSink {
+    /// Rotates at 1 MiB and keeps 3 old files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let file = LogFile { path: path.into(), file: None, len: 0, max_len: 1 << 20, keep: 3 };
+        Self { file: Mutex::new(file) }
+    }
+
+    /// `keep` old files of up to `max_len` bytes each, 0 to just start over
+    pub fn rotate(self, max_len: u64, keep: usize) -> Self {
+        {
+            let mut file = self.file.lock().unwrap();
+            file.max_len = max_len;
+            file.keep = keep;
+        }
+        self
+    }
+}
+
+impl Sink for FileSink {
+    fn write(&self, _name: &str, _record: &Record, line: &str) {
+        // nowhere left to report it
+        let _ = self.file.lock().unwrap().write(line);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Named after the mod and logging to `Mods/<mod>/logs/<mod>.log` and `ring::LOG_RING`, add a
+/// `Ue4ssSink` to also see it in the UE4SS console:
+///
+/// ```ignore
+/// let _ = mod_logger().level(LevelFilter::Debug).sink(Ue4ssSink::new()).init();
+/// ```
+#[cfg(windows)]
+pub fn mod_logger() -> Logger {
+    let dir = &*crate::ue4ss::MOD_DIR;
+    let name = dir.file_name().map_or("mod".into(), |name| name.to_string_lossy());
+    Logger::new(&name)
+        .sink(FileSink::new(dir.join("logs").join(format!("{}.log", name))))
+        .sink(&crate::ring::LOG_RING)
+}
+
+// RC::Output::OutputDevice, the destructor is first like any msvc vtable
+#[cfg(windows)]
+#[repr(C)]
+struct DeviceVtable {
+    _destructor: usize,
+    has_optional_arg: unsafe extern "C" fn(*const Device) -> bool,
+    receive: unsafe extern "C" fn(*const Device, *const CxxStringView),
+    receive_with_optional_arg: unsafe extern "C" fn(*const Device, *const CxxStringView, i32),
+}
+
+#[cfg(windows)]
+#[repr(C)]
+struct Device {
+    vtable: *const DeviceVtable,
+}
+
+#[cfg(windows)]
+type fn_get_default_devices_ref = unsafe extern "C" fn() -> *mut CxxVector<CxxUniquePtr<Device>>;
+
+// the console and UE4SS.log, None if this UE4SS doesn't export it
+#[cfg(windows)]
+static DEFAULT_DEVICES: std::sync::LazyLock<Option<fn_get_default_devices_ref>> = std::sync::LazyLock::new(|| {
+    // not `ue4ss::UE4SS`, that logs while it's being set up
+    let ue4ss = unsafe { GetModuleHandleW(widestring::u16cstr!("UE4SS.dll").as_ptr()) };
+    if ue4ss.is_null() {
+        return None;
+    }
+    // msvc name mangling is stupid
+    let addr = unsafe {
+        GetProcAddress(
+            ue4ss,
+            (b"?get_default_devices_ref@DefaultTargets@Output@RC@@SAAEAV?$vector@V?$unique_ptr@VOutputDevice@Output@RC@@U?$default_delete@VOutputDevice@Output@RC@@@std@@@std@@V?$allocator@V?$unique_ptr@VOutputDevice@Output@RC@@U?$default_delete@VOutputDevice@Output@RC@@@std@@@std@@@2@@std@@XZ\0")
+                .as_ptr()
+                .cast(),
+        )
+    };
+    (!addr.is_null()).then(|| unsafe { std::mem::transmute::<FARPROC, fn_get_default_devices_ref>(addr) })
+});
+
+// RC::LogLevel, it's what the console picks colours from
+#[cfg(windows)]
+fn ue4ss_level(level: Level) -> i32 {
+    match level {
+        Level::Error => 4,
+        Level::Warn => 3,
+        Level::Info => 1,
+        Level::Debug | Level::Trace => 2,
+    }
+}
+
+/// Sends lines to UE4SS's own outputs, the console and `UE4SS.log`, coloured by level. UE4SS adds
+/// its own timestamp so the line is just `[mod] target: message`. Does nothing if UE4SS doesn't
+/// export its output devices.
+#[cfg(windows)]
+pub struct Ue4ssSink {
+    // devices aren't meant to be called from several threads at once
+    lock: Mutex<()>,
+}
+
+#[cfg(windows)]
+impl Ue4ssSink {
+    pub fn new() -> Self {
+        Self { lock: Mutex::new(()) }
+    }
+}
+
+#[cfg(windows)]
+impl Default for Ue4ssSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(windows)]
+impl Sink for Ue4ssSink {
+    fn write(&self, name: &str, record: &Record, _line: &str) {
+        let Some(get_default_devices_ref) = *DEFAULT_DEVICES else {
+            return;
+        };
+        // ue4ss lines carry their own newline
+        let text = U16String::from_str(&format!("[{}] {}: {}\n", name, record.target(), record.args()));
+        let view = CxxStringView::borrowed(&text);
+        let level = ue4ss_level(record.level());
+        let _guard = self.lock.lock().unwrap();
+        unsafe {
+            for device in &*get_default_devices_ref() {
+                let vtable = &*device.vtable;
+                if (vtable.has_optional_arg)(device.ptr) {
+                    (vtable.receive_with_optional_arg)(device.ptr, &*view, level);
+                } else {
+                    (vtable.receive)(device.ptr, &*view);
+                }
+            }
+        }
+    }
+}
+
+/// Old name for `log::info!`, goes wherever the logger sends it (nowhere before it's set up)
 pub fn budget_log(s: &str) {
-    #[cfg(debug_assertions)]
-    {}
-    let mut f = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(env!("CARGO_MANIFEST_DIR").to_owned() + "/budget.log")
-        .unwrap();
-    f.write((s.to_string() + "\n").as_bytes()).unwrap();
-    // }
-}
-
-pub fn clear_log() {
-    //#[cfg(debug_assertions)]
-    let mut f = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .open(env!("CARGO_MANIFEST_DIR").to_owned() + "/budget.log")
-        .unwrap();
-
-    f.set_len(0).unwrap();
+    log::log!(target: "budget_log", Level::Info, "{}", s);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gglibrary-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // 7 bytes a line, two to a file
+    fn write_lines(file: &mut LogFile, lines: impl IntoIterator<Item = usize>) {
+        for i in lines {
+            file.write(&format!("line {}", i)).unwrap();
+        }
+    }
+
+    fn log_file(dir: &Path, keep: usize) -> LogFile {
+        LogFile { path: dir.join("x.log"), file: None, len: 0, max_len: 20, keep }
+    }
+
+    fn read(dir: &Path, name: &str) -> Option<String> {
+        std::fs::read_to_string(dir.join(name)).ok()
+    }
+
+    #[test]
+    fn rotates_oldest_last() {
+        let dir = temp_dir("rotate");
+        let mut file = log_file(&dir, 2);
+        write_lines(&mut file, 1..=8);
+        assert_eq!(read(&dir, "x.log").as_deref(), Some("line 7\nline 8\n"));
+        assert_eq!(read(&dir, "x.1.log").as_deref(), Some("line 5\nline 6\n"));
+        assert_eq!(read(&dir, "x.2.log").as_deref(), Some("line 3\nline 4\n"));
+        assert_eq!(read(&dir, "x.3.log"), None);
+
+        // picks up where the last run stopped
+        let mut file = log_file(&dir, 2);
+        write_lines(&mut file, [9, 10]);
+        assert_eq!(read(&dir, "x.log").as_deref(), Some("line 9\nline 10\n"));
+        assert_eq!(read(&dir, "x.1.log").as_deref(), Some("line 7\nline 8\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keep_nothing() {
+        let dir = temp_dir("keep_nothing");
+        let mut file = log_file(&dir, 0);
+        write_lines(&mut file, 1..=5);
+        assert_eq!(read(&dir, "x.log").as_deref(), Some("line 5\n"));
+        assert_eq!(read(&dir, "x.1.log"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_rotations_keep_appending() {
+        let dir = temp_dir("failed_rotation");
+        // nothing can be renamed over a directory that isn't empty
+        std::fs::create_dir_all(dir.join("x.1.log/blocked")).unwrap();
+        let mut file = log_file(&dir, 1);
+        write_lines(&mut file, 1..=4);
+        assert_eq!(read(&dir, "x.log").as_deref(), Some("line 1\nline 2\nline 3\nline 4\n"));
+
+        std::fs::remove_dir_all(dir.join("x.1.log")).unwrap();
+        write_lines(&mut file, [5]);
+        assert_eq!(read(&dir, "x.log").as_deref(), Some("line 5\n"));
+        assert_eq!(read(&dir, "x.1.log").as_deref(), Some("line 1\nline 2\nline 3\nline 4\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
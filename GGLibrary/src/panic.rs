@@ -0,0 +1,183 @@
+use crate::hooks::HookError;
+use log::{error, warn};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+use std::sync::Once;
+
+#[cfg(windows)]
+use std::fs::{File, OpenOptions};
+#[cfg(windows)]
+use std::io::Write;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
+use std::sync::OnceLock;
+#[cfg(windows)]
+use winapi::um::errhandlingapi::AddVectoredExceptionHandler;
+#[cfg(windows)]
+use winapi::um::winnt::{
+    EXCEPTION_POINTERS, LONG, STATUS_ACCESS_VIOLATION, STATUS_HEAP_CORRUPTION, STATUS_ILLEGAL_INSTRUCTION,
+    STATUS_INTEGER_DIVIDE_BY_ZERO, STATUS_IN_PAGE_ERROR, STATUS_PRIVILEGED_INSTRUCTION, STATUS_STACK_OVERFLOW,
+};
+#[cfg(windows)]
+use winapi::vc::excpt::EXCEPTION_CONTINUE_SEARCH;
+
+// what the hook saw, `catch_unwind` only gets the payload
+thread_local! {
+    static LAST_PANIC: RefCell<Option<(String, Backtrace)>> = const { RefCell::new(None) };
+    // how many `contain`s deep this thread is, a panic at 0 takes the game down
+    static CONTAINED: Cell<u32> = const { Cell::new(0) };
+}
+
+static HOOK: Once = Once::new();
+
+// `logs/recent.log`, opened up front since the exception handler can't allocate
+#[cfg(windows)]
+static CRASH_LOG: OnceLock<File> = OnceLock::new();
+// an exception while draining shouldn't drain again
+#[cfg(windows)]
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+// exceptions that take the game down unless something handles them
+#[cfg(windows)]
+const FATAL: [u32; 7] = [
+    STATUS_ACCESS_VIOLATION,
+    STATUS_IN_PAGE_ERROR,
+    STATUS_ILLEGAL_INSTRUCTION,
+    STATUS_PRIVILEGED_INSTRUCTION,
+    STATUS_INTEGER_DIVIDE_BY_ZERO,
+    STATUS_STACK_OVERFLOW,
+    STATUS_HEAP_CORRUPTION,
+];
+
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s,
+        None => payload.downcast_ref::<String>().map_or("Box<dyn Any>", |s| s.as_str()),
+    }
+}
+
+fn describe(info: &PanicHookInfo) -> String {
+    match info.location() {
+        Some(location) => format!("{} at {}", payload_message(info.payload()), location),
+        None => payload_message(info.payload()).to_string(),
+    }
+}
+
+// whatever the log ring has, in case the game is about to go down with it
+fn save_recent_log() {
+    #[cfg(windows)]
+    let _ = crate::ring::LOG_RING.drain_to_mod_dir();
+}
+
+// drains the log ring on the way down, SEH crashes never get near the panic hook. Vectored so it
+// runs before the game's own `__except` blocks get to report the crash and exit.
+#[cfg(windows)]
+fn install_crash_handler() {
+    let dir = crate::ue4ss::MOD_DIR.join("logs");
+    let file = std::fs::create_dir_all(&dir)
+        .and_then(|()| OpenOptions::new().append(true).create(true).open(dir.join("recent.log")));
+    match file {
+        Ok(file) => {
+            let _ = CRASH_LOG.set(file);
+            unsafe { AddVectoredExceptionHandler(0, Some(on_exception)) };
+        }
+        Err(err) => warn!("crashes won't save the log ring: {}", err),
+    }
+}
+
+// first chance, so it also runs for exceptions the game goes on to handle. A drain only appends
+// what's new, so that costs a few lines at most.
+#[cfg(windows)]
+unsafe extern "system" fn on_exception(info: *mut EXCEPTION_POINTERS) -> LONG {
+    let code = unsafe { (*(*info).ExceptionRecord).ExceptionCode };
+    if FATAL.contains(&code)
+        && !DRAINING.swap(true, Ordering::Acquire)
+        && let Some(mut file) = CRASH_LOG.get()
+    {
+        let _ = crate::ring::LOG_RING.drain_with(|line| file.write_all(line));
+        DRAINING.store(false, Ordering::Release);
+    }
+    EXCEPTION_CONTINUE_SEARCH
+}
+
+/// Keeps the message and a backtrace of every panic for `contain` to log, then runs whatever
+/// hook was there before. A panic outside `contain` is logged right away along with the log ring,
+/// it's a crash. `contain` installs it, this is only for panicking before that.
+///
+/// On Windows it also adds a vectored exception handler that drains the log ring to
+/// `logs/recent.log` when an access violation or another fatal SEH exception goes by.
+pub fn install_panic_hook() {
+    HOOK.call_once(|| {
+        #[cfg(windows)]
+        install_crash_handler();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CONTAINED.get() == 0 {
+                error!("panicked: {}\n{}", describe(info), Backtrace::force_capture());
+                save_recent_log();
+            } else {
+                LAST_PANIC.set(Some((describe(info), Backtrace::force_capture())));
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Runs `f`, a panic is logged under `name` with its backtrace and turns into `None` instead of
+/// unwinding into the game (or aborting it). Anything `f` was in the middle of stays half done.
+pub fn contain<R>(name: &str, f: impl FnOnce() -> R) -> Option<R> {
+    install_panic_hook();
+    LAST_PANIC.set(None);
+    CONTAINED.set(CONTAINED.get() + 1);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CONTAINED.set(CONTAINED.get() - 1);
+    match result {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            match LAST_PANIC.take() {
+                Some((message, backtrace)) => error!("{} panicked: {}\n{}", name, message, backtrace),
+                None => error!("{} panicked: {}", name, payload_message(payload.as_ref())),
+            }
+            save_recent_log();
+            None
+        }
+    }
+}
+
+/// For `hooks!`, logs how disabling a detour that panicked went
+pub fn disabled_after_panic(name: &str, result: Result<(), HookError>) {
+    match result {
+        Ok(()) => warn!("{} disabled after it panicked", name),
+        Err(err) => error!("failed to disable {} after it panicked: {}", name, err),
+    }
+}
+
+/// Wraps `extern "C"` functions the game calls that aren't hooks, like UE4SS's mod callbacks, in
+/// `contain`. A panic returns `Default::default()`.
+///
+/// ```ignore
+/// gglibrary::contained! {
+///     pub unsafe extern "C" fn on_unreal_init(this: *mut CppUserModBase<Data>) {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// Detours declared with `hooks!` are already contained.
+#[macro_export]
+macro_rules! contained {
+    ($(
+        $(#[$meta:meta])*
+        $vis:vis unsafe extern "C" fn $name:ident($($arg:tt: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+    )*) => {$(
+        $(#[$meta])*
+        $vis unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[allow(unused_unsafe)]
+            let result = $crate::panic::contain(stringify!($name), move || unsafe { $body });
+            result.unwrap_or_default()
+        }
+    )*};
+}
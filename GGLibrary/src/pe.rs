@@ -0,0 +1,356 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+const IMAGE_DOS_SIGNATURE: &[u8] = b"MZ";
+const IMAGE_NT_SIGNATURE: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const SECTION_HEADER_SIZE: usize = 40;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+#[derive(Debug)]
+pub enum PeError {
+    Io(std::io::Error),
+    Truncated,
+    BadSignature,
+    UnknownOptionalHeader(u16),
+}
+
+impl From<std::io::Error> for PeError {
+    fn from(err: std::io::Error) -> PeError {
+        PeError::Io(err)
+    }
+}
+
+impl fmt::Display for PeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeError::Io(err) => write!(f, "{}", err),
+            PeError::Truncated => write!(f, "truncated pe file"),
+            PeError::BadSignature => write!(f, "not a pe file"),
+            PeError::UnknownOptionalHeader(magic) => write!(f, "unknown optional header magic {:#x}", magic),
+        }
+    }
+}
+
+impl std::error::Error for PeError {}
+
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub pointer_to_raw_data: u32,
+    pub size_of_raw_data: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    pub fn range(&self) -> std::ops::Range<usize> {
+        let start = self.virtual_address as usize;
+        let len = match self.virtual_size {
+            0 => self.size_of_raw_data,
+            size => size,
+        };
+        start..start + len as usize
+    }
+}
+
+/// The parts of the headers we care about. Headers are laid out the same on disk and once
+/// mapped, so this parses either.
+#[derive(Clone, Debug)]
+pub struct PeHeaders {
+    pub timestamp: u32,
+    pub checksum: u32,
+    pub image_base: u64,
+    pub size_of_image: u32,
+    pub size_of_headers: u32,
+    pub sections: Vec<Section>,
+    /// RVA range of the base relocation blocks, empty if there aren't any
+    pub base_relocations: std::ops::Range<usize>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, PeError> {
+    let bytes = data.get(offset..offset + 2).ok_or(PeError::Truncated)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, PeError> {
+    let bytes = data.get(offset..offset + 4).ok_or(PeError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, PeError> {
+    let bytes = data.get(offset..offset + 8).ok_or(PeError::Truncated)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+impl PeHeaders {
+    /// `.text`, or the whole image if there isn't one. Code signatures are searched for and
+    /// checked for uniqueness there and nowhere else, so every tool agrees on what's unique.
+    pub fn code_range(&self, image_len: usize) -> Range<usize> {
+        self.section(".text")
+            .map(|section| section.range())
+            .map_or(0..image_len, |range| range.start.min(image_len)..range.end.min(image_len))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, PeError> {
+        if data.get(..2) != Some(IMAGE_DOS_SIGNATURE) {
+            return Err(PeError::BadSignature);
+        }
+        let nt = read_u32(data, 0x3c)? as usize;
+        if data.get(nt..nt + 4) != Some(IMAGE_NT_SIGNATURE) {
+            return Err(PeError::BadSignature);
+        }
+
+        let file_header = nt + 4;
+        let number_of_sections = read_u16(data, file_header + 2)? as usize;
+        let timestamp = read_u32(data, file_header + 4)?;
+        let size_of_optional_header = read_u16(data, file_header + 16)? as usize;
+
+        let optional_header = file_header + 20;
+        // the data directories follow NumberOfRvaAndSizes
+        let (image_base, rva_and_sizes) = match read_u16(data, optional_header)? {
+            PE32_PLUS_MAGIC => (read_u64(data, optional_header + 24)?, optional_header + 108),
+            PE32_MAGIC => (read_u32(data, optional_header + 28)? as u64, optional_header + 92),
+            magic => return Err(PeError::UnknownOptionalHeader(magic)),
+        };
+        let size_of_image = read_u32(data, optional_header + 56)?;
+        let size_of_headers = read_u32(data, optional_header + 60)?;
+        let checksum = read_u32(data, optional_header + 64)?;
+        let base_relocations = if read_u32(data, rva_and_sizes)? as usize > IMAGE_DIRECTORY_ENTRY_BASERELOC {
+            let directory = rva_and_sizes + 4 + IMAGE_DIRECTORY_ENTRY_BASERELOC * 8;
+            let start = read_u32(data, directory)? as usize;
+            start..start + read_u32(data, directory + 4)? as usize
+        } else {
+            0..0
+        };
+
+        let section_table = optional_header + size_of_optional_header;
+        let sections = (0..number_of_sections)
+            .map(|i| {
+                let header = section_table + i * SECTION_HEADER_SIZE;
+                let name = data.get(header..header + 8).ok_or(PeError::Truncated)?;
+                let name = String::from_utf8_lossy(name).trim_end_matches('\0').to_string();
+                Ok(Section {
+                    name,
+                    virtual_size: read_u32(data, header + 8)?,
+                    virtual_address: read_u32(data, header + 12)?,
+                    size_of_raw_data: read_u32(data, header + 16)?,
+                    pointer_to_raw_data: read_u32(data, header + 20)?,
+                    characteristics: read_u32(data, header + 36)?,
+                })
+            })
+            .collect::<Result<Vec<_>, PeError>>()?;
+
+        Ok(Self { timestamp, checksum, image_base, size_of_image, size_of_headers, sections, base_relocations })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+/// `PeHeaders::code_range` of a mapped image, the whole image if its headers don't parse
+pub fn code_range(image: &[u8]) -> Range<usize> {
+    PeHeaders::parse(image).map_or(0..image.len(), |headers| headers.code_range(image.len()))
+}
+
+/// A PE file mapped the way the loader would, so offsets into `image` are RVAs and line up with
+/// `memory::module_image` in the game process
+pub struct PeImage {
+    pub headers: PeHeaders,
+    pub image: Vec<u8>,
+}
+
+impl PeImage {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PeError> {
+        Self::map(&std::fs::read(path)?)
+    }
+
+    pub fn map(file: &[u8]) -> Result<Self, PeError> {
+        let headers = PeHeaders::parse(file)?;
+        let mut image = vec![0; headers.size_of_image as usize];
+
+        let header_len = (headers.size_of_headers as usize).min(file.len()).min(image.len());
+        image[..header_len].copy_from_slice(&file[..header_len]);
+        for section in &headers.sections {
+            // virtual size 0 means the raw size is all there is, the rest of a section is zeroed
+            let len = match section.virtual_size {
+                0 => section.size_of_raw_data,
+                size => size.min(section.size_of_raw_data),
+            } as usize;
+            let raw = section.pointer_to_raw_data as usize;
+            let va = section.virtual_address as usize;
+            let src = file.get(raw..raw + len).ok_or(PeError::Truncated)?;
+            image.get_mut(va..va + len).ok_or(PeError::Truncated)?.copy_from_slice(src);
+        }
+
+        Ok(Self { headers, image })
+    }
+
+    pub fn section_data(&self, name: &str) -> Option<&[u8]> {
+        let range = self.headers.section(name)?.range();
+        self.image.get(range)
+    }
+
+    /// RVA ranges the loader patches with the real image base, sorted. Bytes in these change
+    /// every launch so they can't be in a signature.
+    pub fn relocations(&self) -> Result<Vec<std::ops::Range<usize>>, PeError> {
+        let blocks = self.image.get(self.headers.base_relocations.clone()).ok_or(PeError::Truncated)?;
+        let mut relocations = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= blocks.len() {
+            let page = read_u32(blocks, offset)? as usize;
+            let block_size = read_u32(blocks, offset + 4)? as usize;
+            if block_size < 8 {
+                break;
+            }
+            for entry in (offset + 8..offset + block_size).step_by(2) {
+                let entry = read_u16(blocks, entry)?;
+                let rva = page + (entry & 0xfff) as usize;
+                match entry >> 12 {
+                    IMAGE_REL_BASED_DIR64 => relocations.push(rva..rva + 8),
+                    IMAGE_REL_BASED_HIGHLOW => relocations.push(rva..rva + 4),
+                    // padding, or types x64 never uses
+                    _ => {}
+                }
+            }
+            offset += block_size;
+        }
+        relocations.sort_by_key(|range| range.start);
+        Ok(relocations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = &[0x48, 0x89, 0x5c, 0x24, 0x08, 0x48, 0xb8, 0, 0, 0, 0x40, 0x01, 0, 0, 0, 0, 0xc3];
+
+    fn put(file: &mut [u8], offset: usize, bytes: &[u8]) {
+        file[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // (name, virtual address, virtual size, raw pointer, raw size)
+    fn section(file: &mut [u8], i: usize, name: &[u8], va: u32, vsize: u32, raw: u32, raw_size: u32) {
+        let header = 0x148 + i * SECTION_HEADER_SIZE;
+        put(file, header, name);
+        put(file, header + 8, &vsize.to_le_bytes());
+        put(file, header + 12, &va.to_le_bytes());
+        put(file, header + 16, &raw_size.to_le_bytes());
+        put(file, header + 20, &raw.to_le_bytes());
+    }
+
+    // a PE32+ with `.text` at 0x1000 and `.reloc` at 0x2000, headers in the first 0x400 bytes
+    fn fixture() -> Vec<u8> {
+        let mut file = vec![0; 0x800];
+        put(&mut file, 0, b"MZ");
+        put(&mut file, 0x3c, &0x40u32.to_le_bytes());
+        put(&mut file, 0x40, b"PE\0\0");
+        // file header: 2 sections, optional header of 0xf0
+        put(&mut file, 0x46, &2u16.to_le_bytes());
+        put(&mut file, 0x48, &0x6543_2100u32.to_le_bytes());
+        put(&mut file, 0x54, &0xf0u16.to_le_bytes());
+        // optional header
+        put(&mut file, 0x58, &PE32_PLUS_MAGIC.to_le_bytes());
+        put(&mut file, 0x58 + 24, &0x1_4000_0000u64.to_le_bytes());
+        put(&mut file, 0x58 + 56, &0x3000u32.to_le_bytes());
+        put(&mut file, 0x58 + 60, &0x400u32.to_le_bytes());
+        put(&mut file, 0x58 + 64, &0x1234u32.to_le_bytes());
+        put(&mut file, 0x58 + 108, &16u32.to_le_bytes());
+        put(&mut file, 0x58 + 112 + 5 * 8, &0x2000u32.to_le_bytes());
+        put(&mut file, 0x58 + 112 + 5 * 8 + 4, &12u32.to_le_bytes());
+        section(&mut file, 0, b".text", 0x1000, TEXT.len() as u32, 0x400, 0x200);
+        section(&mut file, 1, b".reloc", 0x2000, 12, 0x600, 0x200);
+        put(&mut file, 0x400, TEXT);
+        // one block for the page at 0x1000: the mov's imm64, then a HIGHLOW
+        put(&mut file, 0x600, &0x1000u32.to_le_bytes());
+        put(&mut file, 0x604, &12u32.to_le_bytes());
+        put(&mut file, 0x608, &(IMAGE_REL_BASED_DIR64 << 12 | 0x007).to_le_bytes());
+        put(&mut file, 0x60a, &(IMAGE_REL_BASED_HIGHLOW << 12 | 0x001).to_le_bytes());
+        file
+    }
+
+    #[test]
+    fn parses_headers() {
+        let headers = PeHeaders::parse(&fixture()).unwrap();
+        assert_eq!(headers.timestamp, 0x6543_2100);
+        assert_eq!(headers.checksum, 0x1234);
+        assert_eq!(headers.image_base, 0x1_4000_0000);
+        assert_eq!(headers.size_of_image, 0x3000);
+        assert_eq!(headers.base_relocations, 0x2000..0x200c);
+        let names: Vec<&str> = headers.sections.iter().map(|section| section.name.as_str()).collect();
+        assert_eq!(names, [".text", ".reloc"]);
+        assert_eq!(headers.section(".text").unwrap().range(), 0x1000..0x1000 + TEXT.len());
+    }
+
+    #[test]
+    fn maps_sections_and_relocations() {
+        let pe = PeImage::map(&fixture()).unwrap();
+        assert_eq!(pe.image.len(), 0x3000);
+        assert_eq!(&pe.image[..2], b"MZ");
+        assert_eq!(pe.section_data(".text"), Some(TEXT));
+        // only the virtual size is copied, the rest of the raw data is padding
+        assert_eq!(pe.image[0x1000 + TEXT.len()], 0);
+        assert_eq!(pe.relocations().unwrap(), [0x1001..0x1005, 0x1007..0x100f]);
+    }
+
+    #[test]
+    fn code_ranges() {
+        let file = fixture();
+        let headers = PeHeaders::parse(&file).unwrap();
+        assert_eq!(headers.code_range(0x3000), 0x1000..0x1011);
+        // clamped to a short image
+        assert_eq!(headers.code_range(0x1008), 0x1000..0x1008);
+        assert_eq!(headers.code_range(0x800), 0x800..0x800);
+        assert_eq!(code_range(&PeImage::map(&file).unwrap().image), 0x1000..0x1011);
+        // no `.text` or no headers at all, everything is code
+        let mut renamed = file.clone();
+        put(&mut renamed, 0x148, b"CODE\0");
+        assert_eq!(code_range(&renamed), 0..0x800);
+        assert_eq!(code_range(&[0x90; 64]), 0..64);
+    }
+
+    #[test]
+    fn bad_headers() {
+        assert!(matches!(PeHeaders::parse(b"MZ"), Err(PeError::Truncated)));
+        assert!(matches!(PeHeaders::parse(b"ZM\0\0"), Err(PeError::BadSignature)));
+
+        // e_lfanew past the end of the file, or at something that isn't `PE\0\0`
+        let mut file = fixture();
+        put(&mut file, 0x3c, &0x7000u32.to_le_bytes());
+        assert!(matches!(PeHeaders::parse(&file), Err(PeError::BadSignature)));
+        put(&mut file, 0x3c, &u32::MAX.to_le_bytes());
+        assert!(matches!(PeHeaders::parse(&file), Err(PeError::BadSignature)));
+        put(&mut file, 0x3c, &0x44u32.to_le_bytes());
+        assert!(matches!(PeHeaders::parse(&file), Err(PeError::BadSignature)));
+
+        let mut file = fixture();
+        put(&mut file, 0x58, &0x107u16.to_le_bytes());
+        assert!(matches!(PeHeaders::parse(&file), Err(PeError::UnknownOptionalHeader(0x107))));
+
+        // the section table runs off the end
+        assert!(matches!(PeHeaders::parse(&fixture()[..0x160]), Err(PeError::Truncated)));
+    }
+
+    #[test]
+    fn sections_past_the_end() {
+        // raw data past the end of the file
+        let mut file = fixture();
+        section(&mut file, 0, b".text", 0x1000, 0x20, 0x7f0, 0x200);
+        assert!(matches!(PeImage::map(&file), Err(PeError::Truncated)));
+        // or mapped past SizeOfImage
+        let mut file = fixture();
+        section(&mut file, 0, b".text", 0x2ff0, 0x20, 0x400, 0x200);
+        assert!(matches!(PeImage::map(&file), Err(PeError::Truncated)));
+        // a relocation directory past the image
+        let mut file = fixture();
+        put(&mut file, 0x58 + 112 + 5 * 8, &0x4000u32.to_le_bytes());
+        assert!(matches!(PeImage::map(&file).unwrap().relocations(), Err(PeError::Truncated)));
+    }
+}
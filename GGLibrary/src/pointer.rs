@@ -0,0 +1,418 @@
+use crate::cache::AddressCache;
+use crate::memory::{resolve_in, Operand};
+use crate::pe::code_range;
+use crate::region::{try_read_in, ReadError, RegionQuery};
+use crate::signature::Signature;
+use crate::sigtable::{ResolveError, SignatureTable};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a path starts, both end up as an offset into the image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathStart {
+    /// unique match of a signature, `sig("48 8B 1D ? ? ? ?")`
+    Sig(Signature),
+    /// an entry of the mod's signature table, `entry("RED_SaveData")`
+    Entry(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// `.rip(3, 7)`, see `Operand::Rip`
+    Rip { offset: usize, len: usize },
+    /// `.branch()`, see `Operand::Branch`
+    Branch,
+    /// `+0x1e0` or `-0x40`
+    Add(isize),
+    /// `->`, reads the pointer stored at the current address
+    Deref,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Rip { offset, len } => write!(f, ".rip({}, {})", offset, len),
+            Step::Branch => write!(f, ".branch()"),
+            Step::Add(n) if *n < 0 => write!(f, "-{:#x}", n.unsigned_abs()),
+            Step::Add(n) => write!(f, "+{:#x}", n),
+            Step::Deref => write!(f, "->"),
+        }
+    }
+}
+
+/// How to get from a signature to a game global, one hop at a time:
+///
+/// ```text
+/// entry("RED_SaveData") -> +0x1e0
+/// sig("48 8B 1D ? ? ? ?").rip(3, 7) -> +0x1e0 +0x282f4
+/// ```
+///
+/// `.rip`/`.branch` only go right after the start, everything up to the first `->` is resolved
+/// in the image and the rest in live memory. Serializes as the string above so offsets can live
+/// in a config file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PointerPath {
+    pub start: PathStart,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepError {
+    /// a `.rip`/`.branch` read or landed outside the image
+    OutOfImage,
+    Overflow,
+    /// `->` read a null pointer
+    Null,
+    Read(ReadError),
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepError::OutOfImage => write!(f, "outside the image"),
+            StepError::Overflow => write!(f, "address overflowed"),
+            StepError::Null => write!(f, "read a null pointer"),
+            StepError::Read(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathError {
+    Start(ResolveError),
+    /// `step` is the index into `PointerPath::steps`
+    Step { step: usize, hop: Step, reason: StepError },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Start(err) => write!(f, "start: {}", err),
+            PathError::Step { step, hop, reason } => write!(f, "step {} ({}): {}", step, hop, reason),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathParseError {
+    /// byte offset into the path string
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PathParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for PathParseError {}
+
+impl PointerPath {
+    // steps before the first deref, the part that's resolved in the image
+    fn static_len(&self) -> usize {
+        self.steps.iter().position(|step| *step == Step::Deref).unwrap_or(self.steps.len())
+    }
+
+    /// Image offset of everything before the first `->`. `cache` is only used for `entry` starts.
+    pub fn resolve_static(
+        &self,
+        image: &[u8],
+        table: Option<&SignatureTable>,
+        cache: &mut AddressCache,
+    ) -> Result<usize, PathError> {
+        let mut addr = match &self.start {
+            PathStart::Sig(sig) => sig.scan_unique_in(image, code_range(image)).map_err(|err| PathError::Start(ResolveError::Scan(err)))?,
+            PathStart::Entry(name) => table
+                .ok_or(ResolveError::UnknownName)
+                .and_then(|table| table.resolve_cached(image, name, cache))
+                .map_err(PathError::Start)?,
+        };
+        for (i, &hop) in self.steps[..self.static_len()].iter().enumerate() {
+            let operand = match hop {
+                Step::Rip { offset, len } => Operand::Rip { offset, len },
+                Step::Branch => Operand::Branch,
+                Step::Add(n) => Operand::Add(n),
+                Step::Deref => unreachable!(),
+            };
+            addr = resolve_in(image, addr, &[operand])
+                .filter(|&addr| addr < image.len() || matches!(hop, Step::Add(_)))
+                .ok_or(PathError::Step { step: i, hop, reason: StepError::OutOfImage })?;
+        }
+        Ok(addr)
+    }
+
+    /// Follows the steps from the first `->` on, starting at `addr` which is the static part
+    /// already turned into a live address. Pointers are only read if `regions` says they're
+    /// readable.
+    pub fn follow(&self, mut addr: usize, regions: &dyn RegionQuery) -> Result<usize, PathError> {
+        let skip = self.static_len();
+        for (i, &hop) in self.steps.iter().enumerate().skip(skip) {
+            let fail = |reason| PathError::Step { step: i, hop, reason };
+            addr = match hop {
+                Step::Deref => match unsafe { try_read_in(regions, addr as *const usize) } {
+                    Ok(0) => return Err(fail(StepError::Null)),
+                    Ok(ptr) => ptr,
+                    Err(err) => return Err(fail(StepError::Read(err))),
+                },
+                Step::Add(n) => addr.checked_add_signed(n).ok_or_else(|| fail(StepError::Overflow))?,
+                // the parser keeps these in the static part
+                Step::Rip { .. } | Step::Branch => return Err(fail(StepError::OutOfImage)),
+            };
+        }
+        Ok(addr)
+    }
+}
+
+impl fmt::Display for PointerPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.start {
+            PathStart::Sig(sig) => write!(f, "sig(\"{}\")", sig)?,
+            PathStart::Entry(name) => write!(f, "entry(\"{}\")", name)?,
+        }
+        for step in &self.steps {
+            match step {
+                Step::Rip { .. } | Step::Branch => write!(f, "{}", step)?,
+                _ => write!(f, " {}", step)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        self.pos = self.s.len() - self.rest().trim_start().len();
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, PathParseError> {
+        Err(PathParseError { position: self.pos, message: message.into() })
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), PathParseError> {
+        if self.eat(token) { Ok(()) } else { self.error(format!("expected `{}`", token)) }
+    }
+
+    fn string(&mut self) -> Result<&'a str, PathParseError> {
+        self.expect("\"")?;
+        let Some(len) = self.rest().find('"') else {
+            return self.error("unterminated string");
+        };
+        let s = &self.rest()[..len];
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn number(&mut self) -> Result<usize, PathParseError> {
+        self.skip_ws();
+        let len = self.rest().find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(self.rest().len());
+        let token = &self.rest()[..len];
+        let parsed = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => token.parse(),
+        };
+        match parsed {
+            Ok(n) => {
+                self.pos += len;
+                Ok(n)
+            }
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    fn offset(&mut self, negative: bool) -> Result<Step, PathParseError> {
+        let position = self.pos;
+        let n = isize::try_from(self.number()?)
+            .map_err(|_| PathParseError { position, message: "offset too large".to_string() })?;
+        Ok(Step::Add(if negative { -n } else { n }))
+    }
+
+    fn path(&mut self) -> Result<PointerPath, PathParseError> {
+        let start = if self.eat("sig(") {
+            let position = self.pos;
+            let sig = Signature::from_ida(self.string()?)
+                .map_err(|err| PathParseError { position, message: err.to_string() })?;
+            PathStart::Sig(sig)
+        } else if self.eat("entry(") {
+            PathStart::Entry(self.string()?.to_string())
+        } else {
+            return self.error("expected `sig(` or `entry(`");
+        };
+        self.expect(")")?;
+
+        let mut steps = Vec::new();
+        while self.eat(".") {
+            if self.eat("rip(") {
+                let offset = self.number()?;
+                self.expect(",")?;
+                let len = self.number()?;
+                steps.push(Step::Rip { offset, len });
+            } else if self.eat("branch(") {
+                steps.push(Step::Branch);
+            } else {
+                return self.error("expected `rip(` or `branch(`");
+            }
+            self.expect(")")?;
+        }
+        loop {
+            if self.eat("->") {
+                steps.push(Step::Deref);
+            } else if self.eat("+") {
+                steps.push(self.offset(false)?);
+            } else if self.eat("-") {
+                steps.push(self.offset(true)?);
+            } else if self.rest().is_empty() {
+                break;
+            } else {
+                return self.error("expected `->`, `+` or `-`");
+            }
+        }
+        Ok(PointerPath { start, steps })
+    }
+}
+
+impl FromStr for PointerPath {
+    type Err = PathParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser { s, pos: 0 }.path()
+    }
+}
+
+impl TryFrom<String> for PointerPath {
+    type Error = PathParseError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PointerPath> for String {
+    fn from(path: PointerPath) -> String {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::RegionMap;
+
+    fn parse_error(s: &str) -> usize {
+        s.parse::<PointerPath>().unwrap_err().position
+    }
+
+    #[test]
+    fn parses_entries() {
+        let path: PointerPath = "entry(\"RED_SaveData\") -> +0x1e0".parse().unwrap();
+        assert_eq!(path.start, PathStart::Entry("RED_SaveData".into()));
+        assert_eq!(path.steps, [Step::Deref, Step::Add(0x1e0)]);
+        assert_eq!(path.to_string(), "entry(\"RED_SaveData\") -> +0x1e0");
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let path: PointerPath = "sig(\"48 8B 1D ? ? ? ?\").rip(3, 7) -> -0x40".parse().unwrap();
+        assert_eq!(path.start, PathStart::Sig(Signature::from_ida("48 8B 1D ? ? ? ?").unwrap()));
+        assert_eq!(path.steps, [Step::Rip { offset: 3, len: 7 }, Step::Deref, Step::Add(-0x40)]);
+
+        let shown = path.to_string();
+        assert_eq!(shown, "sig(\"48 8b 1d ? ? ? ?\").rip(3, 7) -> -0x40");
+        assert_eq!(shown.parse::<PointerPath>().unwrap(), path);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(parse_error(""), 0);
+        assert_eq!(parse_error("entry(\"X"), 7);
+        assert_eq!(parse_error("entry(\"X\" -> +1"), 10);
+        assert_eq!(parse_error("entry(\"X\").call()"), 11);
+        assert_eq!(parse_error("entry(\"X\") + zz"), 13);
+        assert_eq!(parse_error("entry(\"X\") -> *"), 14);
+        assert_eq!(parse_error("sig(\"48 GG\")"), 4);
+        assert_eq!(parse_error("entry(\"X\") +0xffffffffffffffff"), 12);
+    }
+
+    #[test]
+    fn serializes_as_a_string() {
+        let path: PointerPath = "entry(\"RED_SaveData\") -> +0x1e0 +0x282f4".parse().unwrap();
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, r#""entry(\"RED_SaveData\") -> +0x1e0 +0x282f4""#);
+        assert_eq!(serde_json::from_str::<PointerPath>(&json).unwrap(), path);
+        assert!(serde_json::from_str::<PointerPath>(r#""entry(\"X\") ->>""#).is_err());
+    }
+
+    #[test]
+    fn resolves_in_the_image() {
+        let mut image = vec![0xcc; 0x40];
+        // mov rbx, [rip + 0x10]
+        image[0x10..0x17].copy_from_slice(&[0x48, 0x8b, 0x1d, 0x10, 0x00, 0x00, 0x00]);
+        let path: PointerPath = "sig(\"48 8B 1D ? ? ? ?\").rip(3, 7) -0x4 -> +0x8".parse().unwrap();
+        assert_eq!(path.resolve_static(&image, None, &mut AddressCache::default()), Ok(0x23));
+
+        let path: PointerPath = "entry(\"RED_SaveData\") -> +0x8".parse().unwrap();
+        assert_eq!(
+            path.resolve_static(&image, None, &mut AddressCache::default()),
+            Err(PathError::Start(ResolveError::UnknownName))
+        );
+    }
+
+    #[test]
+    fn follows_pointers() {
+        let inner = Box::new([0usize, 0, 0x5000, 0]);
+        let outer = Box::new([inner.as_ptr() as usize, 0]);
+        let regions = RegionMap::new();
+        regions.insert_slice(&*outer, true);
+
+        let path: PointerPath = "entry(\"X\") -> +0x10 -> -0x8".parse().unwrap();
+        let start = outer.as_ptr() as usize;
+        assert_eq!(
+            path.follow(start, &regions),
+            Err(PathError::Step {
+                step: 2,
+                hop: Step::Deref,
+                reason: StepError::Read(ReadError::Unreadable(inner.as_ptr() as usize + 0x10)),
+            })
+        );
+
+        regions.insert_slice(&*inner, true);
+        assert_eq!(path.follow(start, &regions), Ok(0x4ff8));
+
+        let path: PointerPath = "entry(\"X\") -> +0x8 ->".parse().unwrap();
+        assert_eq!(
+            path.follow(start, &regions),
+            Err(PathError::Step { step: 2, hop: Step::Deref, reason: StepError::Null })
+        );
+
+        let path: PointerPath = "entry(\"X\") -> -0x10".parse().unwrap();
+        let low = Box::new([8usize]);
+        regions.insert_slice(&*low, true);
+        assert_eq!(
+            path.follow(low.as_ptr() as usize, &regions),
+            Err(PathError::Step { step: 1, hop: Step::Add(-0x10), reason: StepError::Overflow })
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::red::{ECharaID, EColorID};
+use crate::region::{default_regions, ReadError};
+use std::ffi::c_void;
+
+/// One field of a `game_struct!`, `offset` is from the start of the struct
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    pub type_name: &'static str,
+}
+
+/// A `repr(C)` game type declared with `game_struct!`
+pub trait GameStruct: GameValue {
+    const FIELDS: &'static [Field];
+
+    fn field(name: &str) -> Option<&'static Field> {
+        Self::FIELDS.iter().find(|field| field.name == name)
+    }
+}
+
+/// Anything that can show up in a dump, one `path = value` line per leaf
+pub trait GameValue {
+    fn dump(&self, path: &str, out: &mut Vec<String>);
+
+    // arrays of numbers go on one line instead of one line per element
+    fn dump_array(items: &[Self], path: &str, out: &mut Vec<String>)
+    where
+        Self: Sized,
+    {
+        for (i, item) in items.iter().enumerate() {
+            item.dump(&format!("{}[{}]", path, i), out);
+        }
+    }
+}
+
+/// `path.name`, or just `name` at the top
+pub fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_string() } else { format!("{}.{}", path, name) }
+}
+
+macro_rules! number_values {
+    ($($ty:ty),*) => {$(
+        impl GameValue for $ty {
+            fn dump(&self, path: &str, out: &mut Vec<String>) {
+                out.push(format!("{} = {}", path, self));
+            }
+
+            fn dump_array(items: &[Self], path: &str, out: &mut Vec<String>) {
+                out.push(format!("{} = {:?}", path, items));
+            }
+        }
+    )*};
+}
+
+number_values!(i8, i16, i32, i64, u16, u32, u64, f32, f64, bool);
+
+// byte arrays are mostly padding, hex like `print_memory`
+impl GameValue for u8 {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {}", path, self));
+    }
+
+    fn dump_array(items: &[Self], path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {}", path, hex::encode(items)));
+    }
+}
+
+impl<T> GameValue for *mut T {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {:p}", path, *self));
+    }
+}
+
+impl<T> GameValue for *const T {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {:p}", path, *self));
+    }
+}
+
+impl<T: GameValue, const N: usize> GameValue for [T; N] {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        T::dump_array(self, path, out);
+    }
+}
+
+impl GameValue for ECharaID {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {} ({:#x})", path, self, *self as u32));
+    }
+}
+
+impl GameValue for EColorID {
+    fn dump(&self, path: &str, out: &mut Vec<String>) {
+        out.push(format!("{} = {}", path, self.0));
+    }
+}
+
+/// Every field of `value`, one per line:
+///
+/// ```text
+/// chara_history[0].chara_id = KYK (0x1)
+/// chara_history[0].color_id = 14
+/// ```
+pub fn dump<T: GameStruct>(value: &T) -> String {
+    let mut out = Vec::new();
+    value.dump("", &mut out);
+    out.join("\n")
+}
+
+/// `dump` for a struct in game memory, as long as all of it is readable. The bytes still have to
+/// be a valid `T`, an out of range enum is still UB.
+pub unsafe fn dump_at<T: GameStruct>(ptr: *const c_void) -> Result<String, ReadError> {
+    default_regions().check_readable(ptr as usize, size_of::<T>())?;
+    Ok(dump(unsafe { &*(ptr as *const T) }))
+}
+
+/// Declares `repr(C)` structs that implement `GameStruct`. `#[offset(..)]` on a field is checked
+/// at compile time, so a layout mistake doesn't build instead of reading garbage in game:
+///
+/// ```ignore
+/// game_struct! {
+///     #[repr(C)]
+///     pub struct SDecideInfoHistory {
+///         pub chara_history: [SDecideInfo; 2],
+///         #[offset(0x40)]
+///         pub is_valid: u8,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! game_struct {
+    ($(
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[offset($offset:expr)])?
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    )*) => {$(
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $ty,)*
+        }
+
+        impl $crate::reflect::GameStruct for $name {
+            const FIELDS: &'static [$crate::reflect::Field] = &[$(
+                $crate::reflect::Field {
+                    name: stringify!($field),
+                    offset: ::std::mem::offset_of!($name, $field),
+                    size: ::std::mem::size_of::<$ty>(),
+                    type_name: stringify!($ty),
+                },
+            )*];
+        }
+
+        impl $crate::reflect::GameValue for $name {
+            fn dump(&self, path: &str, out: &mut Vec<String>) {
+                $($crate::reflect::GameValue::dump(&self.$field, &$crate::reflect::join(path, stringify!($field)), out);)*
+            }
+        }
+
+        $($(
+            const _: () = assert!(
+                ::std::mem::offset_of!($name, $field) == $offset,
+                concat!(stringify!($name), "::", stringify!($field), " isn't at ", stringify!($offset)),
+            );
+        )?)*
+    )*};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::game_struct! {
+        #[repr(C)]
+        struct Inner {
+            chara_id: ECharaID,
+            color_id: EColorID,
+            flags: u8,
+        }
+
+        #[repr(C)]
+        struct Outer {
+            inner: [Inner; 2],
+            #[offset(0x18)]
+            scores: [i32; 3],
+            pad: [u8; 4],
+            ratio: f32,
+            #[offset(0x30)]
+            ptr: *const u8,
+            grid: [[i16; 2]; 2],
+        }
+    }
+
+    fn outer() -> Outer {
+        Outer {
+            inner: [
+                Inner { chara_id: ECharaID::SOL, color_id: EColorID(1), flags: 3 },
+                Inner { chara_id: ECharaID::MAY, color_id: EColorID(14), flags: 0 },
+            ],
+            scores: [1, -2, 3],
+            pad: [0x00, 0xff, 0x10, 0xab],
+            ratio: 0.5,
+            ptr: std::ptr::null(),
+            grid: [[1, 2], [-3, 4]],
+        }
+    }
+
+    #[test]
+    fn dumps() {
+        let expected = [
+            "inner[0].chara_id = SOL (0x0)",
+            "inner[0].color_id = 1",
+            "inner[0].flags = 3",
+            "inner[1].chara_id = MAY (0x2)",
+            "inner[1].color_id = 14",
+            "inner[1].flags = 0",
+            "scores = [1, -2, 3]",
+            "pad = 00ff10ab",
+            "ratio = 0.5",
+            "ptr = 0x0",
+            "grid[0] = [1, 2]",
+            "grid[1] = [-3, 4]",
+        ];
+        assert_eq!(dump(&outer()), expected.join("\n"));
+    }
+
+    #[test]
+    fn fields() {
+        let names: Vec<&str> = Outer::FIELDS.iter().map(|field| field.name).collect();
+        assert_eq!(names, ["inner", "scores", "pad", "ratio", "ptr", "grid"]);
+        assert_eq!(
+            Outer::field("pad"),
+            Some(&Field { name: "pad", offset: 0x24, size: 4, type_name: "[u8; 4]" })
+        );
+        assert_eq!(Inner::field("flags").map(|field| field.offset), Some(8));
+        assert_eq!(Outer::field("missing"), None);
+    }
+}
@@ -0,0 +1,285 @@
+use std::fmt;
+use std::sync::Mutex;
+
+#[cfg(windows)]
+use winapi::um::memoryapi::VirtualQuery;
+#[cfg(windows)]
+use winapi::um::winnt::{
+    MEMORY_BASIC_INFORMATION, MEM_COMMIT, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY,
+    PAGE_GUARD, PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    Null,
+    /// `ptr + len` doesn't fit in the address space
+    Overflow,
+    /// first address in the range that isn't committed and readable
+    Unreadable(usize),
+    /// first address in the range that isn't committed and writable
+    Unwritable(usize),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Null => write!(f, "null pointer"),
+            ReadError::Overflow => write!(f, "range overflows the address space"),
+            ReadError::Unreadable(addr) => write!(f, "{:#x} isn't readable", addr),
+            ReadError::Unwritable(addr) => write!(f, "{:#x} isn't writable", addr),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// A run of pages with the same state, `end` is exclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub readable: bool,
+    pub writable: bool,
+}
+
+/// Whatever knows the process's memory layout
+pub trait RegionQuery: Sync {
+    /// The region containing `addr`, `None` if nothing is mapped there
+    fn region(&self, addr: usize) -> Option<Region>;
+
+    /// Checks every byte of `addr..addr + len` is in a readable region
+    fn check_readable(&self, addr: usize, len: usize) -> Result<(), ReadError> {
+        check_regions(self, addr, len, |region| region.readable, ReadError::Unreadable)
+    }
+
+    /// Checks every byte of `addr..addr + len` is in a writable region
+    fn check_writable(&self, addr: usize, len: usize) -> Result<(), ReadError> {
+        check_regions(self, addr, len, |region| region.writable, ReadError::Unwritable)
+    }
+}
+
+fn check_regions<Q: RegionQuery + ?Sized>(
+    regions: &Q,
+    addr: usize,
+    len: usize,
+    allowed: fn(&Region) -> bool,
+    denied: fn(usize) -> ReadError,
+) -> Result<(), ReadError> {
+    if addr == 0 {
+        return Err(ReadError::Null);
+    }
+    let end = addr.checked_add(len).ok_or(ReadError::Overflow)?;
+    let mut cursor = addr;
+    while cursor < end {
+        match regions.region(cursor) {
+            // a region that doesn't move the cursor forward would loop forever
+            Some(region) if allowed(&region) && region.start <= cursor && region.end > cursor => cursor = region.end,
+            _ => return Err(denied(cursor)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub struct VirtualQueryRegions;
+
+#[cfg(windows)]
+impl RegionQuery for VirtualQueryRegions {
+    fn region(&self, addr: usize) -> Option<Region> {
+        let mut info: MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+        let written = unsafe { VirtualQuery(addr as *const _, &mut info, size_of::<MEMORY_BASIC_INFORMATION>()) };
+        if written == 0 {
+            return None;
+        }
+        let writable_protect = PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+        let readable_protect = writable_protect | PAGE_READONLY | PAGE_EXECUTE_READ;
+        let usable = info.State == MEM_COMMIT && info.Protect & (PAGE_GUARD | PAGE_NOACCESS) == 0;
+        let start = info.BaseAddress as usize;
+        Some(Region {
+            start,
+            end: start + info.RegionSize,
+            readable: usable && info.Protect & readable_protect != 0,
+            writable: usable && info.Protect & writable_protect != 0,
+        })
+    }
+}
+
+/// VirtualQuery in the game, the global fake map everywhere else
+pub fn default_regions() -> &'static dyn RegionQuery {
+    #[cfg(windows)]
+    {
+        &VirtualQueryRegions
+    }
+    #[cfg(not(windows))]
+    {
+        &FAKE_REGIONS
+    }
+}
+
+#[cfg(not(windows))]
+pub static FAKE_REGIONS: RegionMap = RegionMap::new();
+
+/// Regions set up by hand, anything not inserted is unmapped. Later inserts win where they
+/// overlap earlier ones. A readable region is writable too unless it's `insert_read_only`.
+pub struct RegionMap {
+    regions: Mutex<Vec<Region>>,
+}
+
+impl RegionMap {
+    pub const fn new() -> Self {
+        Self { regions: Mutex::new(Vec::new()) }
+    }
+
+    pub fn insert(&self, start: usize, end: usize, readable: bool) {
+        self.regions.lock().unwrap().push(Region { start, end, readable, writable: readable });
+    }
+
+    /// Like `.rdata`, readable but not writable
+    pub fn insert_read_only(&self, start: usize, end: usize) {
+        self.regions.lock().unwrap().push(Region { start, end, readable: true, writable: false });
+    }
+
+    /// Maps the memory behind a slice, e.g. a test buffer
+    pub fn insert_slice<T>(&self, slice: &[T], readable: bool) {
+        let start = slice.as_ptr() as usize;
+        self.insert(start, start + size_of_val(slice), readable);
+    }
+
+    pub fn clear(&self) {
+        self.regions.lock().unwrap().clear();
+    }
+}
+
+impl Default for RegionMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegionQuery for RegionMap {
+    fn region(&self, addr: usize) -> Option<Region> {
+        let regions = self.regions.lock().unwrap();
+        let top = regions.iter().rev().find(|region| (region.start..region.end).contains(&addr))?;
+        // cut the region short where a later insert starts inside it
+        let end = regions
+            .iter()
+            .rev()
+            .take_while(|region| !std::ptr::eq(*region, top))
+            .filter(|region| region.start > addr && region.start < top.end)
+            .map(|region| region.start)
+            .min()
+            .unwrap_or(top.end);
+        Some(Region { start: addr.max(top.start), end, ..*top })
+    }
+}
+
+/// Copies a `T` from `ptr` if the memory behind it is readable, for pointers found by scanning
+/// or offsets that might be wrong after a patch. Only as safe as the check, something could
+/// still free the memory in between.
+///
+/// `T` must be fine with whatever bytes are there, no `bool`s or enums.
+pub unsafe fn try_read<T: Copy>(ptr: *const T) -> Result<T, ReadError> {
+    unsafe { try_read_in(default_regions(), ptr) }
+}
+
+pub unsafe fn try_read_in<T: Copy>(regions: &dyn RegionQuery, ptr: *const T) -> Result<T, ReadError> {
+    regions.check_readable(ptr as usize, size_of::<T>())?;
+    Ok(unsafe { ptr.read_unaligned() })
+}
+
+/// `try_read` for `len` consecutive `T`s
+pub unsafe fn try_read_slice<T: Copy>(ptr: *const T, len: usize) -> Result<Vec<T>, ReadError> {
+    unsafe { try_read_slice_in(default_regions(), ptr, len) }
+}
+
+pub unsafe fn try_read_slice_in<T: Copy>(
+    regions: &dyn RegionQuery,
+    ptr: *const T,
+    len: usize,
+) -> Result<Vec<T>, ReadError> {
+    let size = size_of::<T>().checked_mul(len).ok_or(ReadError::Overflow)?;
+    regions.check_readable(ptr as usize, size)?;
+    Ok((0..len).map(|i| unsafe { ptr.add(i).read_unaligned() }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(start: usize, end: usize, readable: bool) -> Option<Region> {
+        Some(Region { start, end, readable, writable: readable })
+    }
+
+    #[test]
+    fn later_inserts_win() {
+        let map = RegionMap::new();
+        map.insert(0x1000, 0x3000, true);
+        map.insert(0x2000, 0x2800, false);
+        assert_eq!(map.region(0x1800), region(0x1800, 0x2000, true));
+        assert_eq!(map.region(0x2400), region(0x2400, 0x2800, false));
+        assert_eq!(map.region(0x2800), region(0x2800, 0x3000, true));
+        assert_eq!(map.region(0x3000), None);
+        assert_eq!(map.check_readable(0x1000, 0x1800), Err(ReadError::Unreadable(0x2000)));
+        assert_eq!(map.check_readable(0x2800, 0x800), Ok(()));
+
+        // and the other way around, the readable one covers the unreadable one completely
+        map.insert(0x1000, 0x3000, true);
+        assert_eq!(map.check_readable(0x1000, 0x2000), Ok(()));
+    }
+
+    #[test]
+    fn read_only_regions() {
+        let map = RegionMap::new();
+        map.insert(0x1000, 0x2000, true);
+        map.insert_read_only(0x1800, 0x2000);
+        assert_eq!(map.check_readable(0x1000, 0x1000), Ok(()));
+        assert_eq!(map.check_writable(0x1000, 0x800), Ok(()));
+        assert_eq!(map.check_writable(0x1000, 0x1000), Err(ReadError::Unwritable(0x1800)));
+        assert_eq!(map.check_writable(0, 8), Err(ReadError::Null));
+    }
+
+    #[test]
+    fn gap_in_the_middle() {
+        let map = RegionMap::new();
+        map.insert(0x1000, 0x2000, true);
+        map.insert(0x3000, 0x4000, true);
+        assert_eq!(map.check_readable(0x1800, 0x2000), Err(ReadError::Unreadable(0x2000)));
+        // back to back is one readable range
+        map.insert(0x2000, 0x3000, true);
+        assert_eq!(map.check_readable(0x1800, 0x2000), Ok(()));
+    }
+
+    #[test]
+    fn touching_the_end() {
+        let map = RegionMap::new();
+        map.insert(0x1000, 0x2000, true);
+        assert_eq!(map.check_readable(0x1ff8, 8), Ok(()));
+        assert_eq!(map.check_readable(0x1ff8, 9), Err(ReadError::Unreadable(0x2000)));
+        assert_eq!(map.check_readable(0x2000, 1), Err(ReadError::Unreadable(0x2000)));
+        assert_eq!(map.check_readable(0x2000, 0), Ok(()));
+    }
+
+    #[test]
+    fn null_and_overflow() {
+        let map = RegionMap::new();
+        map.insert(0, usize::MAX, true);
+        assert_eq!(map.check_readable(0, 8), Err(ReadError::Null));
+        assert_eq!(map.check_readable(usize::MAX - 3, 8), Err(ReadError::Overflow));
+        assert_eq!(unsafe { try_read_in(&map, std::ptr::null::<u64>()) }, Err(ReadError::Null));
+        let ptr = 0x1000 as *const u64;
+        assert_eq!(unsafe { try_read_slice_in(&map, ptr, usize::MAX / 4) }, Err(ReadError::Overflow));
+    }
+
+    #[test]
+    fn reads_mapped_buffers() {
+        let map = RegionMap::new();
+        let buffer = [1u32, 2, 3, 4];
+        map.insert_slice(&buffer[..2], true);
+        assert_eq!(unsafe { try_read_in(&map, buffer.as_ptr().add(1)) }, Ok(2));
+        assert_eq!(unsafe { try_read_slice_in(&map, buffer.as_ptr(), 2) }, Ok(vec![1, 2]));
+        let end = buffer.as_ptr() as usize + 8;
+        assert_eq!(unsafe { try_read_slice_in(&map, buffer.as_ptr(), 3) }, Err(ReadError::Unreadable(end)));
+        map.clear();
+        assert_eq!(map.region(buffer.as_ptr() as usize), None);
+    }
+}
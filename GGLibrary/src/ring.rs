@@ -0,0 +1,593 @@
+use crate::output::{Sink, Timestamp};
+use log::{Level, LevelFilter, Record};
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use winapi::shared::minwindef::{FARPROC, HMODULE};
+#[cfg(windows)]
+use winapi::um::libloaderapi::GetProcAddress;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::GetCurrentProcess;
+#[cfg(windows)]
+use winapi::um::psapi::EnumProcessModules;
+
+// longer lines are cut, the file log still has all of them
+const LINE: usize = 256;
+
+/// The ring `output::mod_logger` writes to, every mod has its own since every mod links its own
+/// gglibrary. Outside the mod it's read through `gglibrary_query_log`.
+pub static LOG_RING: LogRing<1024> = LogRing::new();
+
+// a seqlock, `seq` is odd while a writer owns the slot
+struct Slot {
+    // 2 * index + 1 while it's written, 2 * index + 2 once it's done, 0 if it never was
+    seq: AtomicU64,
+    time: AtomicU64,
+    // level in the low byte, text length above it
+    meta: AtomicU64,
+    text: [AtomicU64; LINE / 8],
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            time: AtomicU64::new(0),
+            meta: AtomicU64::new(0),
+            text: [const { AtomicU64::new(0) }; LINE / 8],
+        }
+    }
+}
+
+/// One record out of a `LogRing`, `text` is `target: message`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// counts up from 0 for every record the ring was given, each mod's ring counts on its own
+    pub seq: u64,
+    /// unix time in ms
+    pub time: u64,
+    #[serde(serialize_with = "serialize_level", deserialize_with = "deserialize_level")]
+    pub level: Level,
+    /// the mod whose ring it came from
+    pub module: String,
+    pub text: String,
+}
+
+fn serialize_level<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+fn deserialize_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+    let level = String::deserialize(deserializer)?;
+    level.parse().map_err(|_| D::Error::invalid_value(Unexpected::Str(&level), &"a log level"))
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_line(f, self.time, self.level, &self.module, &self.text)
+    }
+}
+
+fn write_line(f: &mut impl fmt::Write, time: u64, level: Level, module: &str, text: &str) -> fmt::Result {
+    let time = Timestamp(UNIX_EPOCH + Duration::from_millis(time));
+    write!(f, "{} {:<5} [{}] {}", time, level, module, text)
+}
+
+// a record copied out of its slot, a `LogRecord` that's still on the stack
+struct RawRecord {
+    seq: u64,
+    time: u64,
+    level: Level,
+    len: usize,
+    bytes: [u8; LINE],
+}
+
+impl RawRecord {
+    fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.bytes[..self.len])
+    }
+}
+
+// not boxed, the crash drain can't allocate
+#[allow(clippy::large_enum_variant)]
+enum SlotRead {
+    Done(RawRecord),
+    /// a newer record took the slot, this one is gone for good
+    Lapped,
+    /// not written yet or being written right now
+    Pending,
+}
+
+// a line for `drain_with`, anything past the end is cut
+struct LineBuf {
+    bytes: [u8; LINE + 128],
+    len: usize,
+}
+
+impl fmt::Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.len().min(self.bytes.len() - self.len);
+        self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+/// Which records `LogRing::query` and `query_mods` return, everything by default
+#[derive(Clone, Debug)]
+pub struct LogQuery {
+    level: LevelFilter,
+    module: Option<String>,
+    contains: Option<String>,
+    after: Option<u64>,
+    limit: usize,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Self { level: LevelFilter::Trace, module: None, contains: None, after: None, limit: usize::MAX }
+    }
+
+    /// `level` and anything more severe
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Only the mod called `module`, the name it logs under
+    pub fn module(mut self, module: &str) -> Self {
+        self.module = Some(module.to_string());
+        self
+    }
+
+    pub fn contains(mut self, s: &str) -> Self {
+        self.contains = Some(s.to_string());
+        self
+    }
+
+    /// Only records newer than `seq`, for polling with the last `seq` seen. Every mod counts on
+    /// its own, so across mods it only makes sense together with `module`.
+    pub fn after(mut self, seq: u64) -> Self {
+        self.after = Some(seq);
+        self
+    }
+
+    /// The newest `limit` matches
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        record.level <= self.level
+            && self.module.as_ref().is_none_or(|module| record.module == *module)
+            && self.contains.as_ref().is_none_or(|s| record.text.contains(s.as_str()))
+            && self.after.is_none_or(|seq| record.seq > seq)
+    }
+}
+
+impl Default for LogQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The last `N` log records, for looking at while the game runs. Writing never blocks: a record
+/// that would have to wait for another thread still writing the same slot is dropped instead.
+///
+/// `panic::install_panic_hook` drains it to `logs/recent.log` next to the mod when a panic gets
+/// that far or a fatal SEH exception like an access violation goes by.
+pub struct LogRing<const N: usize> {
+    module: OnceLock<String>,
+    // index of the next record
+    head: AtomicU64,
+    // everything before this is already in a file
+    drained: AtomicU64,
+    dropped: AtomicU64,
+    slots: [Slot; N],
+}
+
+impl<const N: usize> LogRing<N> {
+    pub const fn new() -> Self {
+        Self {
+            module: OnceLock::new(),
+            head: AtomicU64::new(0),
+            drained: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            slots: [const { Slot::new() }; N],
+        }
+    }
+
+    pub fn push(&self, level: Level, time: u64, text: &str) {
+        let index = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[(index % N as u64) as usize];
+        let writing = 2 * index + 1;
+        // still being written, or already lapped by a newer record
+        let current = slot.seq.load(Ordering::Relaxed);
+        if current % 2 == 1
+            || current >= writing
+            || slot.seq.compare_exchange(current, writing, Ordering::Acquire, Ordering::Relaxed).is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        // keeps the stores below from being seen before the odd seq, or a reader could take new
+        // data with the old seq for a finished record
+        fence(Ordering::Release);
+
+        let mut len = text.len().min(LINE);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0u8; LINE];
+        bytes[..len].copy_from_slice(&text.as_bytes()[..len]);
+        for (word, chunk) in slot.text.iter().zip(bytes.chunks_exact(8)).take(len.div_ceil(8)) {
+            word.store(u64::from_le_bytes(chunk.try_into().unwrap()), Ordering::Relaxed);
+        }
+        slot.time.store(time, Ordering::Relaxed);
+        slot.meta.store(level as u64 | (len as u64) << 8, Ordering::Relaxed);
+        slot.seq.store(writing + 1, Ordering::Release);
+    }
+
+    fn read(&self, index: u64) -> Option<LogRecord> {
+        match self.read_raw(index) {
+            SlotRead::Done(raw) => Some(LogRecord {
+                seq: raw.seq,
+                time: raw.time,
+                level: raw.level,
+                module: self.module().to_string(),
+                text: raw.text().into_owned(),
+            }),
+            SlotRead::Lapped | SlotRead::Pending => None,
+        }
+    }
+
+    fn read_raw(&self, index: u64) -> SlotRead {
+        let slot = &self.slots[(index % N as u64) as usize];
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq > 2 * index + 2 {
+            return SlotRead::Lapped;
+        }
+        if seq != 2 * index + 2 {
+            return SlotRead::Pending;
+        }
+        let time = slot.time.load(Ordering::Relaxed);
+        let meta = slot.meta.load(Ordering::Relaxed);
+        let len = (meta >> 8) as usize;
+        let mut bytes = [0u8; LINE];
+        for (word, chunk) in slot.text.iter().zip(bytes.chunks_exact_mut(8)).take(len.div_ceil(8)) {
+            chunk.copy_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+        }
+        fence(Ordering::Acquire);
+        // only a newer writer changes a finished slot
+        if slot.seq.load(Ordering::Relaxed) != seq {
+            return SlotRead::Lapped;
+        }
+        match log_level(meta as u8) {
+            Some(level) => SlotRead::Done(RawRecord { seq: index, time, level, len: len.min(LINE), bytes }),
+            None => SlotRead::Lapped,
+        }
+    }
+
+    /// Whoever logged to it, set by the first record
+    pub fn module(&self) -> &str {
+        self.module.get().map_or("", String::as_str)
+    }
+
+    /// Records that got lost to another thread writing the same slot
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Everything still in the ring, oldest first
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records_from(0)
+    }
+
+    fn records_from(&self, start: u64) -> Vec<LogRecord> {
+        let head = self.head.load(Ordering::Acquire);
+        let start = start.max(head.saturating_sub(N as u64));
+        (start..head).filter_map(|index| self.read(index)).collect()
+    }
+
+    pub fn query(&self, query: &LogQuery) -> Vec<LogRecord> {
+        if query.module.as_ref().is_some_and(|module| module != self.module()) {
+            return Vec::new();
+        }
+        let start = query.after.map_or(0, |seq| seq + 1);
+        let mut records: Vec<LogRecord> =
+            self.records_from(start).into_iter().filter(|record| query.matches(record)).collect();
+        records.drain(..records.len().saturating_sub(query.limit));
+        records
+    }
+
+    /// `query` as json lines, one `LogRecord` each
+    pub fn query_json(&self, query: &LogQuery) -> String {
+        let mut out = String::new();
+        for record in self.query(query) {
+            // a struct of strings and numbers always serializes
+            out += &serde_json::to_string(&record).unwrap();
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Appends every record that hasn't been drained yet to `path`, returns how many
+    pub fn drain_to(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let mut file = BufWriter::new(OpenOptions::new().append(true).create(true).open(path)?);
+        let count = self.drain_with(|line| file.write_all(line))?;
+        file.flush()?;
+        Ok(count)
+    }
+
+    /// Hands every record that hasn't been drained yet to `write`, a line at a time, and returns
+    /// how many. Allocates nothing, so it's fine from a crash handler.
+    ///
+    /// A record that's still being written holds the drain back: the next drain starts at it and
+    /// writes what came after it again, a repeat is better than a hole.
+    pub fn drain_with(&self, mut write: impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<usize> {
+        let head = self.head.load(Ordering::Acquire);
+        let start = self.drained.load(Ordering::Relaxed).max(head.saturating_sub(N as u64));
+        let mut first_gap = head;
+        let mut count = 0;
+        for index in start..head {
+            match self.read_raw(index) {
+                SlotRead::Done(raw) => {
+                    let mut line = LineBuf { bytes: [0; LINE + 128], len: 0 };
+                    let _ = write_line(&mut line, raw.time, raw.level, self.module(), &raw.text());
+                    let _ = line.write_char('\n');
+                    write(&line.bytes[..line.len])?;
+                    count += 1;
+                }
+                SlotRead::Lapped => {}
+                SlotRead::Pending => first_gap = first_gap.min(index),
+            }
+        }
+        self.drained.fetch_max(first_gap, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    /// `drain_to` `logs/recent.log` next to the mod
+    #[cfg(windows)]
+    pub fn drain_to_mod_dir(&self) -> io::Result<PathBuf> {
+        let dir = crate::ue4ss::MOD_DIR.join("logs");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("recent.log");
+        self.drain_to(&path)?;
+        Ok(path)
+    }
+}
+
+impl<const N: usize> Default for LogRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn log_level(level: u8) -> Option<Level> {
+    [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace].into_iter().find(|l| *l as u8 == level)
+}
+
+impl<const N: usize> Sink for &'static LogRing<N> {
+    fn write(&self, name: &str, record: &Record, _line: &str) {
+        if self.module.get().is_none() {
+            let _ = self.module.set(name.to_string());
+        }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64);
+        self.push(record.level(), time, &format!("{}: {}", record.target(), record.args()));
+    }
+}
+
+/// Runs `query` over the records of several mods and sorts them by time, e.g. what a socket
+/// client got from each mod's `gglibrary_query_log`
+pub fn merge(query: &LogQuery, sources: impl IntoIterator<Item = Vec<LogRecord>>) -> Vec<LogRecord> {
+    let mut records: Vec<LogRecord> =
+        sources.into_iter().flatten().filter(|record| query.matches(record)).collect();
+    records.sort_by(|a, b| (a.time, &a.module, a.seq).cmp(&(b.time, &b.module, b.seq)));
+    records.drain(..records.len().saturating_sub(query.limit));
+    records
+}
+
+#[cfg(windows)]
+type QueryLog = unsafe extern "C" fn(u32, *const u8, usize, u64, usize, *mut u8, usize) -> usize;
+
+/// `query` over the log rings of every mod in the game, oldest first. Mods are found by the
+/// `gglibrary_query_log` their DLL exports, so ones built against an older gglibrary are missing.
+#[cfg(windows)]
+pub fn query_mods(query: &LogQuery) -> Vec<LogRecord> {
+    let mut modules: Vec<HMODULE> = vec![std::ptr::null_mut(); 1024];
+    let mut needed = 0;
+    let listed = unsafe {
+        EnumProcessModules(GetCurrentProcess(), modules.as_mut_ptr(), size_of_val(modules.as_slice()) as u32, &mut needed)
+    };
+    if listed == 0 {
+        return Vec::new();
+    }
+    modules.truncate(needed as usize / size_of::<HMODULE>());
+    let sources = modules.into_iter().filter_map(|module| {
+        let addr = unsafe { GetProcAddress(module, c"gglibrary_query_log".as_ptr()) };
+        (!addr.is_null()).then(|| query_through(unsafe { std::mem::transmute::<FARPROC, QueryLog>(addr) }, query))
+    });
+    merge(query, sources)
+}
+
+// one mod's records through its export, whatever doesn't parse is left out
+#[cfg(windows)]
+fn query_through(query_log: QueryLog, query: &LogQuery) -> Vec<LogRecord> {
+    let contains = query.contains.as_deref().map_or((std::ptr::null(), 0), |s| (s.as_ptr(), s.len()));
+    let mut out = Vec::new();
+    loop {
+        let len = unsafe {
+            query_log(
+                query.level as u32,
+                contains.0,
+                contains.1,
+                query.after.unwrap_or(u64::MAX),
+                query.limit,
+                out.as_mut_ptr(),
+                out.len(),
+            )
+        };
+        if len <= out.len() {
+            out.truncate(len);
+            break;
+        }
+        // the mod might log more before the next call, so this can take a few goes
+        out.resize(len, 0);
+    }
+    String::from_utf8_lossy(&out).lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+/// Queries `LOG_RING` from outside the mod, e.g. a UE4SS tab or a socket client. Every mod's DLL
+/// exports one, look it up with `GetProcAddress` in the mod to read. `query_mods` goes through
+/// all of them for one query across mods.
+///
+/// Writes the records matching the filters into `out` as json lines (see `LogRing::query_json`)
+/// and returns how many bytes they take. Nothing is written if that's more than `out_len`, call
+/// again with a bigger buffer.
+///
+/// - `level`: `log::LevelFilter` as a number, 1 is errors only and 5 everything
+/// - `contains`: `contains_len` bytes of utf-8 the text has to contain, or null
+/// - `after`: the last `seq` already seen, `u64::MAX` for everything still in the ring
+/// - `limit`: only the newest `limit`, 0 for no limit
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gglibrary_query_log(
+    level: u32,
+    contains: *const u8,
+    contains_len: usize,
+    after: u64,
+    limit: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> usize {
+    let contains = match contains.is_null() {
+        true => None,
+        false => Some(String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(contains, contains_len) })),
+    };
+    crate::panic::contain("gglibrary_query_log", || {
+        let mut query = LogQuery::new().level(level_filter(level));
+        if let Some(contains) = &contains {
+            query = query.contains(contains);
+        }
+        if after != u64::MAX {
+            query = query.after(after);
+        }
+        if limit != 0 {
+            query = query.limit(limit);
+        }
+        let json = LOG_RING.query_json(&query);
+        if json.len() <= out_len && !out.is_null() {
+            unsafe { std::ptr::copy_nonoverlapping(json.as_ptr(), out, json.len()) };
+        }
+        json.len()
+    })
+    .unwrap_or(0)
+}
+
+fn level_filter(level: u32) -> LevelFilter {
+    LevelFilter::iter().nth(level as usize).unwrap_or(LevelFilter::Trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_as_json_lines() {
+        let ring = LogRing::<4>::new();
+        ring.push(Level::Info, 1, "a: loaded");
+        ring.push(Level::Debug, 2, "a: noise");
+        ring.push(Level::Warn, 3, "b: slow frame");
+        let json = ring.query_json(&LogQuery::new().level(LevelFilter::Info));
+        let lines: Vec<serde_json::Value> = json.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["seq"], 0);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[1]["text"], "b: slow frame");
+        assert_eq!(ring.query(&LogQuery::new().contains("frame").after(0)).len(), 1);
+    }
+
+    #[test]
+    fn filters_and_merges_mods() {
+        let first = LogRing::<4>::new();
+        let second = LogRing::<4>::new();
+        first.module.set("First".to_string()).unwrap();
+        second.module.set("Second".to_string()).unwrap();
+        first.push(Level::Info, 10, "a: one");
+        second.push(Level::Info, 5, "b: two");
+        first.push(Level::Error, 20, "a: three");
+        second.push(Level::Warn, 15, "b: four");
+
+        assert!(first.query(&LogQuery::new().module("Second")).is_empty());
+        assert_eq!(second.query(&LogQuery::new().module("Second")).len(), 2);
+        let all = LogQuery::new();
+        let texts = |records: Vec<LogRecord>| records.into_iter().map(|record| record.text).collect::<Vec<_>>();
+        let sources = || [first.query(&all), second.query(&all)];
+        assert_eq!(texts(merge(&all, sources())), ["b: two", "a: one", "b: four", "a: three"]);
+        assert_eq!(texts(merge(&LogQuery::new().level(LevelFilter::Warn), sources())), ["b: four", "a: three"]);
+        assert_eq!(texts(merge(&LogQuery::new().module("First").limit(1), sources())), ["a: three"]);
+
+        // what `query_mods` reads back from every mod's export
+        let json = first.query_json(&all);
+        let parsed: Vec<LogRecord> = json.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(parsed, first.query(&all));
+    }
+
+    #[test]
+    fn c_query_reports_the_size_it_needs() {
+        LOG_RING.push(Level::Error, 0, "ring test: c query");
+        let contains = "ring test: c query";
+        let query = |out: &mut [u8]| unsafe {
+            gglibrary_query_log(1, contains.as_ptr(), contains.len(), u64::MAX, 0, out.as_mut_ptr(), out.len())
+        };
+        let len = query(&mut []);
+        assert!(len > 0);
+        let mut small = vec![0; len - 1];
+        assert_eq!(query(&mut small), len);
+        assert!(small.iter().all(|&b| b == 0));
+        let mut out = vec![0; len];
+        assert_eq!(query(&mut out), len);
+        assert!(std::str::from_utf8(&out).unwrap().ends_with("\"text\":\"ring test: c query\"}\n"));
+    }
+    #[test]
+    fn drain_waits_for_records_still_being_written() {
+        let ring = LogRing::<4>::new();
+        ring.push(Level::Info, 0, "a");
+        ring.push(Level::Info, 0, "b");
+        ring.push(Level::Info, 0, "c");
+        // pretend "b" is still being written
+        ring.slots[1].seq.store(3, Ordering::Relaxed);
+        let drain = |ring: &LogRing<4>| {
+            let mut lines = Vec::new();
+            ring.drain_with(|line| {
+                let line = std::str::from_utf8(line).unwrap();
+                lines.push(line.rsplit(' ').next().unwrap().trim_end().to_string());
+                Ok(())
+            })
+            .unwrap();
+            lines
+        };
+        assert_eq!(drain(&ring), ["a", "c"]);
+        // "b" finished, it comes out with everything after it
+        ring.slots[1].seq.store(4, Ordering::Relaxed);
+        assert_eq!(drain(&ring), ["b", "c"]);
+        assert!(drain(&ring).is_empty());
+
+        // lapped records don't hold the drain back
+        for text in ["d", "e", "f", "g", "h"] {
+            ring.push(Level::Info, 0, text);
+        }
+        assert_eq!(drain(&ring), ["e", "f", "g", "h"]);
+    }
+}
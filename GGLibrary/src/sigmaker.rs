@@ -0,0 +1,192 @@
+use crate::pe::{code_range, PeImage};
+use crate::signature::Signature;
+use iced_x86::{Decoder, DecoderOptions, Instruction, OpKind};
+use std::fmt;
+use std::ops::Range;
+
+// fewer fixed bytes than this and the first scan matches half the image
+const MIN_FIXED: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MakeError {
+    OutOfImage,
+    /// RVA of an instruction that didn't decode
+    BadInstruction(usize),
+    /// still matched more than once after `len` bytes, `matches` are the other offsets
+    NotUnique { len: usize, matches: Vec<usize> },
+}
+
+impl fmt::Display for MakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MakeError::OutOfImage => write!(f, "outside the image"),
+            MakeError::BadInstruction(rva) => write!(f, "invalid instruction at {:#x}", rva),
+            MakeError::NotUnique { len, matches } => {
+                write!(f, "still {} other matches after {} bytes", matches.len(), len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MakeError {}
+
+fn is_branch(kind: OpKind) -> bool {
+    matches!(kind, OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64)
+}
+
+/// Mask for one instruction, everything that moves between builds is wildcarded: rip
+/// displacements, branch and call targets, and anything the loader relocates
+fn instruction_mask(decoder: &Decoder, instruction: &Instruction, relocations: &[Range<usize>]) -> Vec<u8> {
+    let mut mask = vec![0xff; instruction.len()];
+    let offsets = decoder.get_constant_offsets(instruction);
+    if instruction.is_ip_rel_memory_operand() && offsets.has_displacement() {
+        let start = offsets.displacement_offset();
+        mask[start..start + offsets.displacement_size()].fill(0);
+    }
+    if (0..instruction.op_count()).any(|i| is_branch(instruction.op_kind(i))) && offsets.has_immediate() {
+        let start = offsets.immediate_offset();
+        mask[start..start + offsets.immediate_size()].fill(0);
+    }
+    let start = instruction.ip() as usize;
+    let end = start + instruction.len();
+    let first = relocations.partition_point(|range| range.end <= start);
+    for range in relocations[first..].iter().take_while(|range| range.start < end) {
+        for rva in range.start.max(start)..range.end.min(end) {
+            mask[rva - start] = 0;
+        }
+    }
+    mask
+}
+
+/// The shortest signature that only matches the function at `rva` in `image`'s `.text`, built an
+/// instruction at a time and checked after every fixed byte. `relocations` must be sorted, see
+/// `PeImage::relocations`. Stops at `int3` padding since whatever comes after is another function.
+pub fn make_signature(
+    image: &[u8],
+    relocations: &[Range<usize>],
+    rva: usize,
+    max_len: usize,
+) -> Result<Signature, MakeError> {
+    let text = code_range(image);
+    let code = image.get(rva..).ok_or(MakeError::OutOfImage)?;
+    let code = &code[..code.len().min(max_len + 15)];
+    let mut decoder = Decoder::with_ip(64, code, rva as u64, DecoderOptions::NONE);
+
+    let mut bytes = Vec::new();
+    let mut mask = Vec::new();
+    // offsets still matching, found by a full scan once there's enough to scan for
+    let mut candidates: Option<Vec<usize>> = None;
+    let mut fixed = 0;
+    while decoder.can_decode() && bytes.len() < max_len {
+        let instruction = decoder.decode();
+        if instruction.is_invalid() {
+            return Err(MakeError::BadInstruction(instruction.ip() as usize));
+        }
+        if instruction.mnemonic() == iced_x86::Mnemonic::Int3 {
+            break;
+        }
+        let start = instruction.ip() as usize;
+        let len = bytes.len();
+        bytes.extend_from_slice(&image[start..start + instruction.len()]);
+        mask.extend(instruction_mask(&decoder, &instruction, relocations));
+
+        for i in len..bytes.len().min(max_len) {
+            // a wildcard can't rule anything out
+            if mask[i] == 0 {
+                continue;
+            }
+            fixed += 1;
+            match &mut candidates {
+                None if fixed < MIN_FIXED => continue,
+                None => {
+                    let sig = Signature::new(bytes[..=i].to_vec(), mask[..=i].to_vec()).unwrap();
+                    candidates = Some(sig.scan_all_in(image, text.clone()));
+                }
+                Some(candidates) => candidates.retain(|&offset| {
+                    image.get(offset + i).is_some_and(|&b| b & mask[i] == bytes[i] & mask[i])
+                }),
+            }
+            if candidates.as_deref() == Some(&[rva]) {
+                return Ok(Signature::new(bytes[..=i].to_vec(), mask[..=i].to_vec()).unwrap());
+            }
+        }
+    }
+
+    let len = bytes.len().min(max_len);
+    let candidates = match candidates {
+        Some(candidates) => candidates,
+        // short function, scan for whatever there is
+        None => match Signature::new(bytes[..len].to_vec(), mask[..len].to_vec()) {
+            Ok(sig) if sig.scan_unique_in(image, text.clone()) == Ok(rva) => return Ok(sig),
+            Ok(sig) => sig.scan_all_in(image, text),
+            Err(_) => Vec::new(),
+        },
+    };
+    let matches = candidates.into_iter().filter(|&offset| offset != rva).collect();
+    Err(MakeError::NotUnique { len, matches })
+}
+
+/// `make_signature` for a PE loaded from disk
+pub fn make_signature_pe(pe: &PeImage, rva: usize, max_len: usize) -> Result<Signature, MakeError> {
+    // no relocation table just means nothing gets masked for it
+    let relocations = pe.relocations().unwrap_or_default();
+    make_signature(&pe.image, &relocations, rva, max_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // push rbx; sub rsp, 0x20; mov rax, [rip + disp]; call rel; mov ecx, imm; add rsp, 0x20; pop rbx; ret
+    fn function(disp: u8, call: u8, imm: u8) -> Vec<u8> {
+        let mut code = vec![0x40, 0x53, 0x48, 0x83, 0xec, 0x20];
+        code.extend([0x48, 0x8b, 0x05, disp, 0x00, 0x00, 0x00]);
+        code.extend([0xe8, call, 0x00, 0x00, 0x00]);
+        code.extend([0xb9, imm, 0x12, 0x00, 0x00]);
+        code.extend([0x48, 0x83, 0xc4, 0x20, 0x5b, 0xc3]);
+        code
+    }
+
+    // two functions that only differ in their displacements and the constant
+    fn image() -> Vec<u8> {
+        let mut image = vec![0xcc; 0x80];
+        let a = function(0x10, 0x20, 0x34);
+        let b = function(0x30, 0x40, 0x78);
+        image[0x10..0x10 + a.len()].copy_from_slice(&a);
+        image[0x40..0x40 + b.len()].copy_from_slice(&b);
+        image
+    }
+
+    #[test]
+    fn masks_displacements() {
+        let image = image();
+        let sig = make_signature(&image, &[], 0x10, 64).unwrap();
+        assert_eq!(sig.to_string(), "40 53 48 83 ec 20 48 8b 05 ? ? ? ? e8 ? ? ? ? b9 34");
+        assert_eq!(sig.scan_unique_in(&image, 0..image.len()), Ok(0x10));
+
+        let sig = make_signature(&image, &[], 0x40, 64).unwrap();
+        assert_eq!(sig.to_string(), "40 53 48 83 ec 20 48 8b 05 ? ? ? ? e8 ? ? ? ? b9 78");
+    }
+
+    #[test]
+    fn masks_relocations() {
+        let image = image();
+        // with the constant relocated there's nothing left telling them apart
+        let relocations = [Range { start: 0x23, end: 0x27 }];
+        assert_eq!(
+            make_signature(&image, &relocations, 0x10, 64),
+            Err(MakeError::NotUnique { len: 29, matches: vec![0x40] })
+        );
+        // and a cap hit before the constant
+        assert_eq!(make_signature(&image, &[], 0x10, 16), Err(MakeError::NotUnique { len: 16, matches: vec![0x40] }));
+    }
+
+    #[test]
+    fn bad_starts() {
+        let mut image = image();
+        assert_eq!(make_signature(&image, &[], 0x100, 64), Err(MakeError::OutOfImage));
+        // push es doesn't exist in 64-bit code
+        image[0x70] = 0x06;
+        assert_eq!(make_signature(&image, &[], 0x70, 64), Err(MakeError::BadInstruction(0x70)));
+    }
+}
@@ -0,0 +1,587 @@
+use aho_corasick::AhoCorasick;
+use memchr::memmem::Finder;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A byte pattern with a per-byte mask, parsed once and reused for every scan.
+/// A byte matches when `byte & mask == value`, so `??` is mask `0x00` and `4?` is mask `0xf0`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signature {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    Empty,
+    /// `position` is the index of the offending token (IDA/x64dbg) or byte (code style)
+    InvalidToken { position: usize, token: String },
+    MaskLengthMismatch { pattern: usize, mask: usize },
+    /// every byte is a wildcard so the pattern matches everywhere
+    NoFixedBytes,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Empty => write!(f, "empty signature"),
+            SignatureError::InvalidToken { position, token } => {
+                write!(f, "invalid token {:?} at position {}", token, position)
+            }
+            SignatureError::MaskLengthMismatch { pattern, mask } => {
+                write!(f, "pattern has {} bytes but mask has {}", pattern, mask)
+            }
+            SignatureError::NoFixedBytes => write!(f, "signature only contains wildcards"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanError {
+    NotFound,
+    /// offsets of every match
+    Ambiguous(Vec<usize>),
+}
+
+impl ScanError {
+    /// `Ok` only when `matches` holds exactly one offset
+    pub fn check_unique(matches: Vec<usize>) -> Result<usize, ScanError> {
+        match matches.as_slice() {
+            [] => Err(ScanError::NotFound),
+            [offset] => Ok(*offset),
+            _ => Err(ScanError::Ambiguous(matches)),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::NotFound => write!(f, "not found"),
+            ScanError::Ambiguous(matches) => {
+                write!(f, "ambiguous: {} matches at ", matches.len())?;
+                for (i, offset) in matches.iter().enumerate() {
+                    if i == 8 {
+                        return write!(f, ", ...");
+                    }
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:#x}", offset)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+fn parse_nibble(c: char) -> Option<(u8, u8)> {
+    match c {
+        '?' => Some((0, 0)),
+        _ => c.to_digit(16).map(|d| (d as u8, 0xf)),
+    }
+}
+
+// "48", "4?", "??" or "?"
+fn parse_token(token: &str) -> Option<(u8, u8)> {
+    let chars: Vec<char> = token.chars().collect();
+    match chars.as_slice() {
+        ['?'] => Some((0, 0)),
+        [hi, lo] => {
+            let (hi, hi_mask) = parse_nibble(*hi)?;
+            let (lo, lo_mask) = parse_nibble(*lo)?;
+            Some((hi << 4 | lo, hi_mask << 4 | lo_mask))
+        }
+        _ => None,
+    }
+}
+
+impl Signature {
+    /// IDA style, e.g. `48 8b ? ?? 05`
+    pub fn from_ida(pattern: &str) -> Result<Self, SignatureError> {
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+        for (position, token) in pattern.split_whitespace().enumerate() {
+            let (byte, byte_mask) = parse_token(token).ok_or_else(|| SignatureError::InvalidToken {
+                position,
+                token: token.to_string(),
+            })?;
+            bytes.push(byte);
+            mask.push(byte_mask);
+        }
+        Self::new(bytes, mask)
+    }
+
+    /// Code style, e.g. `\x48\x8b\x00` with mask `xx?`
+    pub fn from_code(pattern: &str, mask: &str) -> Result<Self, SignatureError> {
+        let mut tokens = pattern.split("\\x");
+        let prefix = tokens.next().unwrap_or_default();
+        if !prefix.is_empty() {
+            return Err(SignatureError::InvalidToken { position: 0, token: prefix.to_string() });
+        }
+        let mut bytes = Vec::new();
+        for (position, token) in tokens.enumerate() {
+            let byte = (token.len() == 2 && token.chars().all(|c| c.is_ascii_hexdigit()))
+                .then(|| u8::from_str_radix(token, 16).unwrap())
+                .ok_or_else(|| SignatureError::InvalidToken { position, token: token.to_string() })?;
+            bytes.push(byte);
+        }
+        if bytes.len() != mask.len() {
+            return Err(SignatureError::MaskLengthMismatch { pattern: bytes.len(), mask: mask.len() });
+        }
+        let mut byte_mask = Vec::new();
+        for (position, c) in mask.chars().enumerate() {
+            match c {
+                'x' | 'X' => byte_mask.push(0xff),
+                '?' | '.' => byte_mask.push(0),
+                _ => return Err(SignatureError::InvalidToken { position, token: c.to_string() }),
+            }
+        }
+        Self::new(bytes, byte_mask)
+    }
+
+    /// x64dbg style, e.g. `488B??1D` or `48 8B ?? 1D`, with nibble wildcards like `4?`
+    pub fn from_x64dbg(pattern: &str) -> Result<Self, SignatureError> {
+        let chars: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+        for (position, pair) in chars.chunks(2).enumerate() {
+            let token: String = pair.iter().collect();
+            let parsed = if pair.len() == 2 { parse_token(&token) } else { None };
+            let (byte, byte_mask) =
+                parsed.ok_or(SignatureError::InvalidToken { position, token })?;
+            bytes.push(byte);
+            mask.push(byte_mask);
+        }
+        Self::new(bytes, mask)
+    }
+
+    /// Raw bytes and per-byte masks, bits not in the mask are ignored
+    pub fn new(bytes: Vec<u8>, mask: Vec<u8>) -> Result<Self, SignatureError> {
+        if bytes.len() != mask.len() {
+            return Err(SignatureError::MaskLengthMismatch { pattern: bytes.len(), mask: mask.len() });
+        }
+        if bytes.is_empty() {
+            return Err(SignatureError::Empty);
+        }
+        if mask.iter().all(|&m| m == 0) {
+            return Err(SignatureError::NoFixedBytes);
+        }
+        let bytes = bytes.iter().zip(&mask).map(|(b, m)| b & m).collect();
+        Ok(Self { bytes, mask })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// `data` must be at least `self.len()` bytes
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(&self.mask)
+                .zip(data)
+                .all(|((b, m), d)| d & m == *b)
+    }
+}
+
+impl Signature {
+    /// Offset of the first match in `haystack`
+    pub fn scan(&self, haystack: &[u8]) -> Option<usize> {
+        self.matches_in(haystack).next()
+    }
+
+    /// Offset of the first match starting at or after `start`
+    pub fn scan_from(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        let mut matches = self.matches_in(haystack);
+        matches.pos = start;
+        matches.next()
+    }
+
+    /// Offsets of every match in `haystack`
+    pub fn scan_all(&self, haystack: &[u8]) -> Vec<usize> {
+        self.matches_in(haystack).collect()
+    }
+
+    /// Offset of the only match, a signature that stopped being unique after a patch is an error
+    pub fn scan_unique(&self, haystack: &[u8]) -> Result<usize, ScanError> {
+        ScanError::check_unique(self.scan_all(haystack))
+    }
+
+    /// `scan_all` over `range` of `haystack`, offsets are still into all of `haystack`
+    pub fn scan_all_in(&self, haystack: &[u8], range: Range<usize>) -> Vec<usize> {
+        let start = range.start;
+        let haystack = haystack.get(range).unwrap_or_default();
+        self.matches_in(haystack).map(|offset| offset + start).collect()
+    }
+
+    /// `scan_unique` over `range` of `haystack`, see `pe::code_range`
+    pub fn scan_unique_in(&self, haystack: &[u8], range: Range<usize>) -> Result<usize, ScanError> {
+        ScanError::check_unique(self.scan_all_in(haystack, range))
+    }
+
+    /// Every match in `haystack`, overlapping ones included
+    pub fn matches_in<'a>(&'a self, haystack: &'a [u8]) -> Matches<'a> {
+        Matches::new(self, haystack)
+    }
+
+    // longest run of fully fixed bytes
+    pub(crate) fn anchor(&self) -> (usize, usize) {
+        let mut best = (0, 0);
+        let mut run_start = 0;
+        for (i, &m) in self.mask.iter().chain([0].iter()).enumerate() {
+            if m != 0xff {
+                if i - run_start > best.1 {
+                    best = (run_start, i - run_start);
+                }
+                run_start = i + 1;
+            }
+        }
+        best
+    }
+}
+
+/// Finds the signature's longest fixed run with `memchr::memmem` (SIMD accelerated), every hit
+/// is then checked against the full masked pattern.
+pub struct Matches<'a> {
+    sig: &'a Signature,
+    haystack: &'a [u8],
+    anchor_start: usize,
+    finder: Option<Finder<'a>>,
+    pos: usize,
+}
+
+impl<'a> Matches<'a> {
+    fn new(sig: &'a Signature, haystack: &'a [u8]) -> Self {
+        let (anchor_start, anchor_len) = sig.anchor();
+        // nibble-only signatures have nothing to search for and get checked at every offset
+        let finder = (anchor_len > 0)
+            .then(|| Finder::new(&sig.bytes[anchor_start..anchor_start + anchor_len]));
+        Self { sig, haystack, anchor_start, finder, pos: 0 }
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sig = self.sig;
+        while self.pos + sig.len() <= self.haystack.len() {
+            let candidate = match &self.finder {
+                Some(finder) => {
+                    let from = self.pos + self.anchor_start;
+                    let tail = sig.len() - self.anchor_start - finder.needle().len();
+                    let hit = finder.find(&self.haystack[from..self.haystack.len() - tail])?;
+                    from + hit - self.anchor_start
+                }
+                None => self.pos,
+            };
+            self.pos = candidate + 1;
+            if sig.matches(&self.haystack[candidate..]) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+/// Several signatures searched for in one pass. The anchors of every signature go into a single
+/// Aho-Corasick automaton, so the haystack is only read once however many signatures there are.
+pub struct SignatureSet<'a> {
+    sigs: Vec<&'a Signature>,
+    anchors: AhoCorasick,
+    // anchor pattern id -> (signature index, anchor start), identical anchors share an id
+    owners: Vec<Vec<(usize, usize)>>,
+    // nibble-only signatures, scanned on their own
+    unanchored: Vec<usize>,
+}
+
+impl<'a> SignatureSet<'a> {
+    pub fn new(sigs: impl IntoIterator<Item = &'a Signature>) -> Self {
+        let sigs: Vec<&Signature> = sigs.into_iter().collect();
+        let mut patterns: Vec<&[u8]> = Vec::new();
+        let mut owners: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut unanchored = Vec::new();
+        for (i, sig) in sigs.iter().enumerate() {
+            let (start, len) = sig.anchor();
+            if len == 0 {
+                unanchored.push(i);
+                continue;
+            }
+            let anchor = &sig.bytes[start..start + len];
+            match patterns.iter().position(|p| *p == anchor) {
+                Some(id) => owners[id].push((i, start)),
+                None => {
+                    patterns.push(anchor);
+                    owners.push(vec![(i, start)]);
+                }
+            }
+        }
+        let anchors = AhoCorasick::new(patterns).unwrap();
+        Self { sigs, anchors, owners, unanchored }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sigs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sigs.is_empty()
+    }
+
+    /// Offsets of every match of every signature, indexed like the signatures the set was built
+    /// from. Each list is sorted.
+    pub fn scan_all(&self, haystack: &[u8]) -> Vec<Vec<usize>> {
+        let mut found = vec![Vec::new(); self.sigs.len()];
+        for hit in self.anchors.find_overlapping_iter(haystack) {
+            for &(i, anchor_start) in &self.owners[hit.pattern().as_usize()] {
+                let Some(candidate) = hit.start().checked_sub(anchor_start) else {
+                    continue;
+                };
+                if self.sigs[i].matches(&haystack[candidate..]) {
+                    found[i].push(candidate);
+                }
+            }
+        }
+        for &i in &self.unanchored {
+            found[i] = self.sigs[i].scan_all(haystack);
+        }
+        found
+    }
+}
+
+impl FromStr for Signature {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_ida(s)
+    }
+}
+
+/// Formats as IDA style, full wildcards are written as `?`
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (b, m)) in self.bytes.iter().zip(&self.mask).enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            match m {
+                0xff => write!(f, "{:02x}", b)?,
+                0x00 => write!(f, "?")?,
+                _ => {
+                    let hi = if m & 0xf0 != 0 { format!("{:x}", b >> 4) } else { "?".to_string() };
+                    let lo = if m & 0x0f != 0 { format!("{:x}", b & 0xf) } else { "?".to_string() };
+                    write!(f, "{}{}", hi, lo)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a table of named IDA style signatures, collecting every malformed entry instead of
+/// stopping at the first one
+pub fn parse_signatures<'a>(
+    table: &[(&'a str, &str)],
+) -> Result<HashMap<&'a str, Signature>, Vec<(&'a str, SignatureError)>> {
+    let mut sigs = HashMap::new();
+    let mut errors = Vec::new();
+    for &(name, pattern) in table {
+        match Signature::from_ida(pattern) {
+            Ok(sig) => {
+                sigs.insert(name, sig);
+            }
+            Err(err) => errors.push((name, err)),
+        }
+    }
+    if errors.is_empty() { Ok(sigs) } else { Err(errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sig(bytes: &[u8], mask: &[u8]) -> Signature {
+        Signature::new(bytes.to_vec(), mask.to_vec()).unwrap()
+    }
+
+    fn invalid(position: usize, token: &str) -> SignatureError {
+        SignatureError::InvalidToken { position, token: token.to_string() }
+    }
+
+    #[test]
+    fn parses_ida() {
+        let cases = [
+            ("48 8b 05", sig(&[0x48, 0x8b, 0x05], &[0xff, 0xff, 0xff])),
+            ("48 8B ? ?? 05", sig(&[0x48, 0x8b, 0, 0, 0x05], &[0xff, 0xff, 0, 0, 0xff])),
+            ("4? ?b", sig(&[0x40, 0x0b], &[0xf0, 0x0f])),
+            ("  e8 \t ?  ", sig(&[0xe8, 0], &[0xff, 0])),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(Signature::from_ida(pattern), Ok(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn ida_errors() {
+        let cases = [
+            ("", SignatureError::Empty),
+            ("   ", SignatureError::Empty),
+            ("? ?? ?", SignatureError::NoFixedBytes),
+            ("48 8g 05", invalid(1, "8g")),
+            ("48 8b 5", invalid(2, "5")),
+            ("48 8b 05 488", invalid(3, "488")),
+            ("??? 48", invalid(0, "???")),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(Signature::from_ida(pattern), Err(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn parses_code() {
+        let cases = [
+            ("\\x48\\x8b\\x05", "xxx", sig(&[0x48, 0x8b, 0x05], &[0xff, 0xff, 0xff])),
+            ("\\x48\\x8B\\x00\\x05", "xX?.", sig(&[0x48, 0x8b, 0, 0], &[0xff, 0xff, 0, 0])),
+        ];
+        for (pattern, mask, expected) in cases {
+            assert_eq!(Signature::from_code(pattern, mask), Ok(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn code_errors() {
+        let cases = [
+            ("", "", SignatureError::Empty),
+            ("\\x48\\x00", "??", SignatureError::NoFixedBytes),
+            ("48\\x8b", "xx", invalid(0, "48")),
+            ("\\x48\\x8g\\x05", "xxx", invalid(1, "8g")),
+            ("\\x48\\x8b\\x5", "xxx", invalid(2, "5")),
+            ("\\x48\\x8b", "xxx", SignatureError::MaskLengthMismatch { pattern: 2, mask: 3 }),
+            ("\\x48\\x8b\\x05", "xxy", invalid(2, "y")),
+        ];
+        for (pattern, mask, expected) in cases {
+            assert_eq!(Signature::from_code(pattern, mask), Err(expected), "{} {}", pattern, mask);
+        }
+    }
+
+    #[test]
+    fn parses_x64dbg() {
+        let cases = [
+            ("488B051D", sig(&[0x48, 0x8b, 0x05, 0x1d], &[0xff, 0xff, 0xff, 0xff])),
+            ("48 8B ?? 1D", sig(&[0x48, 0x8b, 0, 0x1d], &[0xff, 0xff, 0, 0xff])),
+            ("4?8B?D", sig(&[0x40, 0x8b, 0x0d], &[0xf0, 0xff, 0x0f])),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(Signature::from_x64dbg(pattern), Ok(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn x64dbg_errors() {
+        let cases = [
+            ("", SignatureError::Empty),
+            ("????", SignatureError::NoFixedBytes),
+            ("488G05", invalid(1, "8G")),
+            ("488B0", invalid(2, "0")),
+            // pairs are read across spaces, an odd digit count leaves half a byte at the end
+            ("48 ?8B", invalid(2, "B")),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(Signature::from_x64dbg(pattern), Err(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn raw_errors() {
+        assert_eq!(
+            Signature::new(vec![0x48, 0x8b], vec![0xff]),
+            Err(SignatureError::MaskLengthMismatch { pattern: 2, mask: 1 })
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for pattern in ["48 8b ? ? 05", "4? ?b e8", "ff 25"] {
+            assert_eq!(Signature::from_ida(pattern).unwrap().to_string(), pattern);
+        }
+    }
+
+    fn ida(pattern: &str) -> Signature {
+        Signature::from_ida(pattern).unwrap()
+    }
+
+    #[test]
+    fn restarts_after_partial_match() {
+        // the first `aa aa` is a false start, the match begins one byte later
+        assert_eq!(ida("aa aa ab").scan_all(&[0xaa, 0xaa, 0xaa, 0xab]), vec![1]);
+        assert_eq!(ida("aa ab").scan_all(&[0xaa, 0xaa, 0xaa, 0xab]), vec![2]);
+        assert_eq!(ida("aa ? ab").scan_all(&[0xaa, 0xaa, 0xaa, 0xab, 0xab]), vec![1, 2]);
+        assert_eq!(ida("aa aa").scan_all(&[0xaa; 4]), vec![0, 1, 2]);
+        assert_eq!(ida("aa aa ab").scan_all(&[0xaa, 0xaa, 0xab, 0xaa, 0xaa, 0xaa, 0xab]), vec![0, 4]);
+        assert_eq!(ida("aa aa ab").scan(&[0xaa, 0xaa, 0xaa]), None);
+    }
+
+    #[test]
+    fn nibble_wildcards() {
+        let haystack = [0x48, 0x8b, 0x05, 0x49, 0x8b, 0x0d, 0x58, 0x8b, 0x05];
+        assert_eq!(ida("4? 8b").scan_all(&haystack), vec![0, 3]);
+        assert_eq!(ida("?8 8b ?5").scan_all(&haystack), vec![0, 6]);
+        // nothing fully fixed to anchor on, every offset gets checked
+        assert_eq!(ida("4? ?b").scan_all(&haystack), vec![0, 3]);
+        assert_eq!(ida("?9 8? ?d").scan_unique(&haystack), Ok(3));
+        assert_eq!(ida("?8 8b").scan_unique(&haystack), Err(ScanError::Ambiguous(vec![0, 6])));
+    }
+
+    #[test]
+    fn scan_from_and_edges() {
+        let haystack = [0xe8, 0x01, 0xe8, 0x02, 0xe8];
+        assert_eq!(ida("e8 ?").scan_from(&haystack, 1), Some(2));
+        assert_eq!(ida("e8 ?").scan_from(&haystack, 3), None);
+        assert_eq!(ida("e8 ? e8 ? e8 ?").scan(&haystack), None);
+        assert_eq!(ida("e8").scan_all(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn scans_in_range() {
+        let haystack = [0xe8, 0x01, 0xe8, 0x02, 0xe8, 0x03];
+        assert_eq!(ida("e8 ?").scan_all_in(&haystack, 1..6), vec![2, 4]);
+        assert_eq!(ida("e8 ?").scan_unique_in(&haystack, 1..5), Ok(2));
+    }
+
+    #[test]
+    fn set_matches_single_scans() {
+        let haystack = [0xaa, 0xaa, 0xaa, 0xab, 0x48, 0x8b, 0x05, 0xaa, 0xab];
+        let sigs = [ida("aa aa ab"), ida("aa ab"), ida("4? 8b"), ida("4? ?b"), ida("ff")];
+        let set = SignatureSet::new(&sigs);
+        let expected: Vec<Vec<usize>> = sigs.iter().map(|sig| sig.scan_all(&haystack)).collect();
+        assert_eq!(set.scan_all(&haystack), expected);
+        assert_eq!(expected[1], vec![2, 7]);
+    }
+
+    #[test]
+    fn parse_signatures_collects_every_error() {
+        let errors = parse_signatures(&[("a", "48 8b"), ("b", "zz"), ("c", "?")]).unwrap_err();
+        assert_eq!(errors, vec![("b", invalid(0, "zz")), ("c", SignatureError::NoFixedBytes)]);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::cache::AddressCache;
+use crate::memory::{resolve_in, Operand};
+use crate::pe::code_range;
+use crate::signature::{parse_signatures, ScanError, Signature, SignatureError, SignatureSet};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+
+// `from` chains are a couple of entries long, anything deeper is a cycle
+const MAX_FROM_DEPTH: usize = 8;
+
+/// One named entry of a mod's `signatures.toml`:
+///
+/// ```toml
+/// [RED_SaveData]
+/// pattern = "48 8B 1D ? ? ? ?"
+/// from = "Cmd_chapterclear"
+/// operands = [{ rip = { offset = 3, len = 7 } }]
+/// ```
+///
+/// Entries are scanned for a unique match in `.text` (see `pe::code_range`) unless `from` is set,
+/// in which case the first match at or after that entry's address is used.
+#[derive(Clone, Debug)]
+pub struct SignatureEntry {
+    pub name: String,
+    pub sig: Signature,
+    pub from: Option<String>,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Deserialize)]
+struct RawEntry {
+    pattern: String,
+    from: Option<String>,
+    #[serde(default)]
+    operands: Vec<Operand>,
+}
+
+#[derive(Debug)]
+pub enum TableError {
+    Toml(toml::de::Error),
+    Signatures(Vec<(String, SignatureError)>),
+    UnknownFrom { name: String, from: String },
+}
+
+impl From<toml::de::Error> for TableError {
+    fn from(err: toml::de::Error) -> TableError {
+        TableError::Toml(err)
+    }
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Toml(err) => write!(f, "{}", err),
+            TableError::Signatures(errors) => {
+                for (i, (name, err)) in errors.iter().enumerate() {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "malformed signature {}: {}", name, err)?;
+                }
+                Ok(())
+            }
+            TableError::UnknownFrom { name, from } => {
+                write!(f, "{} is scanned from unknown signature {}", name, from)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    UnknownName,
+    Scan(ScanError),
+    /// the entry this one is scanned from failed
+    From(String),
+    /// an operand pointed outside the image
+    Operands,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::UnknownName => write!(f, "no such signature"),
+            ResolveError::Scan(err) => write!(f, "{}", err),
+            ResolveError::From(from) => write!(f, "{} failed to resolve", from),
+            ResolveError::Operands => write!(f, "operands point outside the image"),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+impl ResolveError {
+    /// One word for tables, what `sigcheck` and `sigmatrix` print. `Ok` is "found".
+    pub fn status(result: &Result<usize, ResolveError>) -> &'static str {
+        match result {
+            Ok(_) => "found",
+            Err(ResolveError::Scan(ScanError::NotFound)) => "missing",
+            Err(ResolveError::Scan(ScanError::Ambiguous(_))) => "ambiguous",
+            Err(_) => "unresolved",
+        }
+    }
+}
+
+/// Every signature a mod declares. Shared between the mod at runtime and `sigcheck` offline, both
+/// resolve against a mapped image so results are RVAs.
+#[derive(Clone, Debug)]
+pub struct SignatureTable {
+    entries: Vec<SignatureEntry>,
+}
+
+impl SignatureTable {
+    pub fn from_toml(s: &str) -> Result<Self, TableError> {
+        let raw: BTreeMap<String, RawEntry> = toml::from_str(s)?;
+        let patterns: Vec<(&str, &str)> =
+            raw.iter().map(|(name, entry)| (name.as_str(), entry.pattern.as_str())).collect();
+        let mut sigs = parse_signatures(&patterns).map_err(|errors| {
+            TableError::Signatures(errors.into_iter().map(|(name, err)| (name.to_string(), err)).collect())
+        })?;
+
+        let mut entries = Vec::new();
+        for (name, entry) in &raw {
+            if let Some(from) = &entry.from
+                && !raw.contains_key(from)
+            {
+                return Err(TableError::UnknownFrom { name: name.clone(), from: from.clone() });
+            }
+            entries.push(SignatureEntry {
+                name: name.clone(),
+                sig: sigs.remove(name.as_str()).unwrap(),
+                from: entry.from.clone(),
+                operands: entry.operands.clone(),
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[SignatureEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SignatureEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// RVA of `name` in `image`
+    pub fn resolve(&self, image: &[u8], name: &str) -> Result<usize, ResolveError> {
+        self.resolve_cached(image, name, &mut AddressCache::default())
+    }
+
+    /// Like `resolve` but checks `cache` first. A cached match is trusted if the signature still
+    /// matches there, otherwise the entry is rescanned and the cache updated.
+    pub fn resolve_cached(
+        &self,
+        image: &[u8],
+        name: &str,
+        cache: &mut AddressCache,
+    ) -> Result<usize, ResolveError> {
+        self.resolve_depth(image, code_range(image), name, 0, cache, &HashMap::new())
+    }
+
+    /// Every entry, found in a single pass over `.text`
+    pub fn resolve_all<'a>(&'a self, image: &[u8]) -> BTreeMap<&'a str, Result<usize, ResolveError>> {
+        self.resolve_batch(image, &mut AddressCache::default())
+    }
+
+    /// Every entry, with whatever `cache` doesn't already have found in a single pass over `.text`.
+    /// `from` entries aren't part of the pass, they tend to be broad and only need scanning from
+    /// where the entry they're scanned from ends up.
+    pub fn resolve_batch<'a>(
+        &'a self,
+        image: &[u8],
+        cache: &mut AddressCache,
+    ) -> BTreeMap<&'a str, Result<usize, ResolveError>> {
+        let range = code_range(image);
+        let pending: Vec<&SignatureEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.from.is_none() && self.cached(image, &range, entry, cache).is_none())
+            .collect();
+        let mut swept = HashMap::new();
+        if !pending.is_empty() {
+            let haystack = image.get(range.clone()).unwrap_or_default();
+            let found = SignatureSet::new(pending.iter().map(|entry| &entry.sig)).scan_all(haystack);
+            for (entry, offsets) in pending.iter().zip(found) {
+                let offsets = offsets.into_iter().map(|offset| offset + range.start).collect();
+                swept.insert(entry.name.as_str(), offsets);
+            }
+        }
+        self.entries
+            .iter()
+            .map(|entry| {
+                let result = self.resolve_depth(image, range.clone(), &entry.name, 0, cache, &swept);
+                (entry.name.as_str(), result)
+            })
+            .collect()
+    }
+
+    // a match cached from before everything was scoped to `.text` gets rescanned
+    fn cached(
+        &self,
+        image: &[u8],
+        range: &Range<usize>,
+        entry: &SignatureEntry,
+        cache: &AddressCache,
+    ) -> Option<usize> {
+        cache
+            .get(&entry.name)
+            .filter(|&inst| range.contains(&inst) && image.get(inst..).is_some_and(|data| entry.sig.matches(data)))
+    }
+
+    // `swept` holds every match of the unique entries `resolve_batch` swept for, anything else is
+    // scanned
+    fn resolve_depth(
+        &self,
+        image: &[u8],
+        range: Range<usize>,
+        name: &str,
+        depth: usize,
+        cache: &mut AddressCache,
+        swept: &HashMap<&str, Vec<usize>>,
+    ) -> Result<usize, ResolveError> {
+        let entry = self.get(name).ok_or(ResolveError::UnknownName)?;
+        let inst = match (self.cached(image, &range, entry, cache), &entry.from) {
+            (Some(inst), _) => inst,
+            (None, Some(from)) => {
+                let start = (depth < MAX_FROM_DEPTH)
+                    .then(|| self.resolve_depth(image, range.clone(), from, depth + 1, cache, swept).ok())
+                    .flatten()
+                    .ok_or_else(|| ResolveError::From(from.clone()))?;
+                entry
+                    .sig
+                    .scan_from(&image[..range.end], start.max(range.start))
+                    .ok_or(ResolveError::Scan(ScanError::NotFound))?
+            }
+            (None, None) => match swept.get(name) {
+                Some(offsets) => ScanError::check_unique(offsets.clone()),
+                None => entry.sig.scan_unique_in(image, range),
+            }
+            .map_err(ResolveError::Scan)?,
+        };
+        match resolve_in(image, inst, &entry.operands) {
+            Some(rva) => {
+                cache.insert(name, inst);
+                Ok(rva)
+            }
+            None => {
+                cache.remove(name);
+                Err(ResolveError::Operands)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheKey;
+
+    const TABLE: &str = r#"
+        [RED_SaveData]
+        pattern = "48 8B 1D ? ? ? ?"
+        operands = [{ rip = { offset = 3, len = 7 } }]
+    "#;
+
+    // `mov rbx, [rip + 0x10]` at `at`
+    fn image_with(at: &[usize]) -> Vec<u8> {
+        let mut image = vec![0xcc; 0x80];
+        for &at in at {
+            image[at..at + 7].copy_from_slice(&[0x48, 0x8b, 0x1d, 0x10, 0x00, 0x00, 0x00]);
+        }
+        image
+    }
+
+    #[test]
+    fn stale_matches_are_rescanned() {
+        let table = SignatureTable::from_toml(TABLE).unwrap();
+        let image = image_with(&[0x10]);
+        let path = std::env::temp_dir().join(format!("gglibrary-{}-stale.toml", std::process::id()));
+        let mut cache = AddressCache::default();
+        cache.insert("RED_SaveData", 0x20);
+        cache.save(&path).unwrap();
+
+        let mut cache = AddressCache::load(&path, CacheKey::default());
+        assert_eq!(table.resolve_cached(&image, "RED_SaveData", &mut cache), Ok(0x27));
+        assert_eq!(cache.get("RED_SaveData"), Some(0x10));
+        assert!(cache.is_dirty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn cached_matches_skip_the_scan() {
+        let table = SignatureTable::from_toml(TABLE).unwrap();
+        // ambiguous if it were scanned
+        let image = image_with(&[0x10, 0x20]);
+        let path = std::env::temp_dir().join(format!("gglibrary-{}-cached.toml", std::process::id()));
+        let key = CacheKey { timestamp: 1, checksum: 2, size_of_image: 0x80 };
+        let mut cache = AddressCache::new(key);
+        cache.insert("RED_SaveData", 0x20);
+        cache.save(&path).unwrap();
+
+        let mut cache = AddressCache::load(&path, key);
+        assert_eq!(table.resolve_cached(&image, "RED_SaveData", &mut cache), Ok(0x37));
+        assert!(!cache.is_dirty());
+
+        let mut cache = AddressCache::load(&path, CacheKey { timestamp: 3, ..key });
+        assert_eq!(
+            table.resolve_cached(&image, "RED_SaveData", &mut cache),
+            Err(ResolveError::Scan(ScanError::Ambiguous(vec![0x10, 0x20])))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    const CHAINS: &str = r#"
+        [Func]
+        pattern = "40 53 48 83 EC 20"
+
+        [Load]
+        pattern = "48 8B 1D ? ? ? ?"
+        from = "Func"
+        operands = [{ rip = { offset = 3, len = 7 } }]
+
+        [Call]
+        pattern = "E8 ? ? ? ?"
+        from = "Load"
+        operands = ["branch"]
+
+        [Dup]
+        pattern = "48 8B 1D ? ? ? ?"
+
+        [Gone]
+        pattern = "0F 0B"
+
+        [Orphan]
+        pattern = "C3"
+        from = "Gone"
+    "#;
+
+    #[test]
+    fn batches_match_single_lookups() {
+        let table = SignatureTable::from_toml(CHAINS).unwrap();
+        let mut image = image_with(&[0x08, 0x30, 0x50]);
+        image[0x20..0x26].copy_from_slice(&[0x40, 0x53, 0x48, 0x83, 0xec, 0x20]);
+        // call 0x20
+        image[0x60..0x65].copy_from_slice(&[0xe8, 0xbb, 0xff, 0xff, 0xff]);
+
+        let single: BTreeMap<&str, _> =
+            table.entries().iter().map(|entry| (entry.name.as_str(), table.resolve(&image, &entry.name))).collect();
+        assert_eq!(single["Func"], Ok(0x20));
+        assert_eq!(single["Load"], Ok(0x47));
+        assert_eq!(single["Call"], Ok(0x20));
+        assert_eq!(single["Dup"], Err(ResolveError::Scan(ScanError::Ambiguous(vec![0x08, 0x30, 0x50]))));
+        assert_eq!(single["Gone"], Err(ResolveError::Scan(ScanError::NotFound)));
+        assert_eq!(single["Orphan"], Err(ResolveError::From("Gone".into())));
+        assert_eq!(table.resolve_all(&image), single);
+
+        // and again with the resolved half cached
+        let mut cache = AddressCache::default();
+        assert_eq!(table.resolve_batch(&image, &mut cache), single);
+        assert_eq!(cache.get("Load"), Some(0x30));
+        assert_eq!(table.resolve_batch(&image, &mut cache), single);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::memory::print_memory;
+use crate::region::{default_regions, ReadError, RegionQuery};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
+use winapi::um::processthreadsapi::GetCurrentProcessId;
+#[cfg(windows)]
+use winapi::um::winuser::{GetAsyncKeyState, GetForegroundWindow, GetWindowThreadProcessId};
+
+/// A copy of some game memory, saved as toml with the bytes in `print_memory`'s hex so a pile of
+/// them can be diffed offline with `snapdiff`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub label: String,
+    /// where the bytes were in the game, only for reference
+    pub addr: usize,
+    /// unix time in ms
+    pub time: u64,
+    pub data: String,
+}
+
+impl Snapshot {
+    /// Copies `len` bytes at `ptr`, as long as all of them are readable
+    pub unsafe fn capture(label: &str, ptr: *const u8, len: usize) -> Result<Self, ReadError> {
+        unsafe { Self::capture_in(default_regions(), label, ptr, len) }
+    }
+
+    pub unsafe fn capture_in(
+        regions: &dyn RegionQuery,
+        label: &str,
+        ptr: *const u8,
+        len: usize,
+    ) -> Result<Self, ReadError> {
+        regions.check_readable(ptr as usize, len)?;
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64);
+        Ok(Self {
+            label: label.to_string(),
+            addr: ptr as usize,
+            time,
+            data: unsafe { print_memory(ptr, len) },
+        })
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>, hex::FromHexError> {
+        hex::decode(&self.data)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        toml::from_str(&std::fs::read_to_string(path)?).map_err(std::io::Error::other)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, toml::to_string(self).map_err(std::io::Error::other)?)
+    }
+
+    /// Saves to `snapshots/<label>-<time>.toml` next to the mod
+    #[cfg(windows)]
+    pub fn save_to_mod_dir(&self) -> std::io::Result<PathBuf> {
+        let dir = crate::ue4ss::MOD_DIR.join("snapshots");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}.toml", self.label, self.time));
+        self.save(&path)?;
+        Ok(path)
+    }
+}
+
+/// Offsets of the `width` byte words that aren't the same in every buffer, the shortest buffer
+/// decides where it stops. A `width` of 0 compares single bytes.
+pub fn changed_offsets(buffers: &[Vec<u8>], width: usize) -> Vec<usize> {
+    let width = width.max(1);
+    let len = buffers.iter().map(Vec::len).min().unwrap_or(0);
+    (0..len)
+        .step_by(width)
+        .filter(|&offset| {
+            let end = (offset + width).min(len);
+            buffers.windows(2).any(|pair| pair[0][offset..end] != pair[1][offset..end])
+        })
+        .collect()
+}
+
+/// A few ways to read the same bytes, for guessing what a field is
+pub fn describe(bytes: &[u8]) -> String {
+    let mut parts = vec![hex::encode(bytes)];
+    if let Some(&b) = bytes.first() {
+        parts.push(format!("u8 {}", b));
+    }
+    if let Some(b) = bytes.first_chunk::<2>() {
+        parts.push(format!("i16 {}", i16::from_le_bytes(*b)));
+    }
+    if let Some(b) = bytes.first_chunk::<4>() {
+        parts.push(format!("i32 {}", i32::from_le_bytes(*b)));
+        parts.push(format!("u32 {:#x}", u32::from_le_bytes(*b)));
+        parts.push(format!("f32 {:?}", f32::from_le_bytes(*b)));
+    }
+    if let Some(b) = bytes.first_chunk::<8>() {
+        parts.push(format!("i64 {}", i64::from_le_bytes(*b)));
+        parts.push(format!("f64 {:?}", f64::from_le_bytes(*b)));
+    }
+    parts.join("  ")
+}
+
+/// A key that fires once per press while the game has focus, poll it from `on_update`:
+///
+/// ```ignore
+/// static SNAPSHOT_KEY: Hotkey = Hotkey::new(0x77); // F8
+/// if SNAPSHOT_KEY.pressed() { ... }
+/// ```
+#[cfg(windows)]
+pub struct Hotkey {
+    vk: i32,
+    down: AtomicBool,
+}
+
+#[cfg(windows)]
+impl Hotkey {
+    pub const fn new(vk: i32) -> Self {
+        Self { vk, down: AtomicBool::new(false) }
+    }
+
+    pub fn pressed(&self) -> bool {
+        // GetAsyncKeyState sees keys pressed in any window
+        let down = game_focused() && unsafe { GetAsyncKeyState(self.vk) } as u16 & 0x8000 != 0;
+        !self.down.swap(down, Ordering::Relaxed) && down
+    }
+}
+
+#[cfg(windows)]
+fn game_focused() -> bool {
+    let window = unsafe { GetForegroundWindow() };
+    if window.is_null() {
+        return false;
+    }
+    let mut pid = 0;
+    unsafe { GetWindowThreadProcessId(window, &mut pid) };
+    pid == unsafe { GetCurrentProcessId() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed() {
+        let buffers = vec![
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
+            vec![0, 1, 2, 3, 4, 9, 6, 7, 8, 10],
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 11],
+        ];
+        assert_eq!(changed_offsets(&buffers, 4), [4, 8]);
+        assert_eq!(changed_offsets(&buffers, 1), [5, 8]);
+        assert_eq!(changed_offsets(&buffers, 0), [5, 8]);
+        assert_eq!(changed_offsets(&buffers, 16), [0]);
+        assert_eq!(changed_offsets(&buffers[..1], 4), [] as [usize; 0]);
+        assert_eq!(changed_offsets(&[], 4), [] as [usize; 0]);
+    }
+
+    #[test]
+    fn describes() {
+        assert_eq!(describe(&[]), "");
+        assert_eq!(describe(&[0xff]), "ff  u8 255");
+        assert_eq!(describe(&[0xfe, 0xff, 0x00]), "feff00  u8 254  i16 -2");
+        assert_eq!(
+            describe(&1.5f32.to_le_bytes()),
+            "0000c03f  u8 0  i16 0  i32 1069547520  u32 0x3fc00000  f32 1.5"
+        );
+        assert_eq!(
+            describe(&1.0f64.to_le_bytes()),
+            "000000000000f03f  u8 0  i16 0  i32 0  u32 0x0  f32 0.0  i64 4607182418800017408  f64 1.0"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = [0x12u8, 0x34, 0x56];
+        let regions = crate::region::RegionMap::new();
+        assert!(unsafe { Snapshot::capture_in(&regions, "data", data.as_ptr(), data.len()) }.is_err());
+        regions.insert_slice(&data, true);
+        let snapshot = unsafe { Snapshot::capture_in(&regions, "data", data.as_ptr(), data.len()) }.unwrap();
+        assert_eq!(snapshot.bytes().unwrap(), data);
+
+        let path = std::env::temp_dir().join(format!("gglibrary-{}-snapshot.toml", std::process::id()));
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use crate::snapshot::Hotkey;
+#[cfg(windows)]
+use log::{info, warn};
+#[cfg(windows)]
+use std::path::PathBuf;
+
+// enough for a p99 of the last minute or so of anything that fires every frame
+const RECENT: usize = 4096;
+
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+// every hook `hooks!` installed, in the order they were
+static HOOKS: Mutex<Vec<(&'static str, &'static HookStats)>> = Mutex::new(Vec::new());
+
+/// Off by default, while it's off a detour only pays for one atomic load
+pub fn set_tracing(on: bool) {
+    TRACING.store(on, Ordering::Relaxed);
+}
+
+pub fn is_tracing() -> bool {
+    TRACING.load(Ordering::Relaxed)
+}
+
+/// Returns whether it's on now
+pub fn toggle_tracing() -> bool {
+    !TRACING.fetch_xor(true, Ordering::Relaxed)
+}
+
+struct Samples {
+    calls: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    // ns of the last `RECENT` calls, `next` wraps around
+    recent: Vec<u32>,
+    next: usize,
+}
+
+impl Samples {
+    const EMPTY: Samples = Samples {
+        calls: 0,
+        total: Duration::ZERO,
+        min: Duration::MAX,
+        max: Duration::ZERO,
+        recent: Vec::new(),
+        next: 0,
+    };
+}
+
+/// Call counts and timings of one detour. `hooks!` keeps one per hook.
+pub struct HookStats {
+    samples: Mutex<Samples>,
+}
+
+/// What `dump` writes per hook, times in µs. `p99_us` only covers the most recent calls.
+///
+/// A call is timed from entering the detour to returning, so the original function is included
+/// whenever the detour called `orig()`. That's how long the game waited on the hook, not what the
+/// detour itself costs: compare against a run with the detour returning straight into `orig()`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HookReport {
+    /// unix time in ms
+    pub time: u64,
+    pub hook: &'static str,
+    pub calls: u64,
+    pub min_us: f64,
+    pub avg_us: f64,
+    pub max_us: f64,
+    pub p99_us: f64,
+}
+
+impl HookStats {
+    pub const fn new() -> Self {
+        Self { samples: Mutex::new(Samples::EMPTY) }
+    }
+
+    pub fn record(&self, elapsed: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.calls += 1;
+        samples.total += elapsed;
+        samples.min = samples.min.min(elapsed);
+        samples.max = samples.max.max(elapsed);
+        let ns = elapsed.as_nanos().min(u32::MAX as u128) as u32;
+        if samples.recent.len() < RECENT {
+            samples.recent.push(ns);
+        } else {
+            let next = samples.next;
+            samples.recent[next] = ns;
+        }
+        samples.next = (samples.next + 1) % RECENT;
+    }
+
+    pub fn reset(&self) {
+        *self.samples.lock().unwrap() = Samples::EMPTY;
+    }
+
+    /// `None` until it's been called with tracing on
+    pub fn report(&self, hook: &'static str) -> Option<HookReport> {
+        let samples = self.samples.lock().unwrap();
+        if samples.calls == 0 {
+            return None;
+        }
+        let mut recent = samples.recent.clone();
+        recent.sort_unstable();
+        let p99 = recent[(recent.len() * 99).div_ceil(100) - 1];
+        let us = |d: Duration| d.as_nanos() as f64 / 1000.0;
+        Some(HookReport {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64),
+            hook,
+            calls: samples.calls,
+            min_us: us(samples.min),
+            avg_us: us(samples.total) / samples.calls as f64,
+            max_us: us(samples.max),
+            p99_us: p99 as f64 / 1000.0,
+        })
+    }
+}
+
+impl Default for HookStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lists `stats` in `dump`, `hooks!` does this for every hook it installs
+pub fn register(hook: &'static str, stats: &'static HookStats) {
+    HOOKS.lock().unwrap().push((hook, stats));
+}
+
+/// For `hooks!`, when a detour was entered. `None` while tracing is off.
+pub fn start() -> Option<Instant> {
+    is_tracing().then(Instant::now)
+}
+
+/// For `hooks!`, when the detour returned
+pub fn finish(stats: &HookStats, start: Option<Instant>) {
+    if let Some(start) = start {
+        stats.record(start.elapsed());
+    }
+}
+
+/// Every hook that's been called with tracing on
+pub fn reports() -> Vec<HookReport> {
+    HOOKS.lock().unwrap().iter().filter_map(|(hook, stats)| stats.report(hook)).collect()
+}
+
+/// Forgets every call so far, e.g. to only measure one match
+pub fn reset() {
+    for (_, stats) in HOOKS.lock().unwrap().iter() {
+        stats.reset();
+    }
+}
+
+/// Appends `reports()` to `path` as json lines, so dumps taken over a session can be compared
+pub fn dump(path: impl AsRef<Path>) -> io::Result<()> {
+    let mut out = String::new();
+    for report in reports() {
+        out += &serde_json::to_string(&report).map_err(io::Error::other)?;
+        out.push('\n');
+    }
+    OpenOptions::new().append(true).create(true).open(path)?.write_all(out.as_bytes())
+}
+
+/// Appends to `trace.jsonl` next to the mod
+#[cfg(windows)]
+pub fn dump_to_mod_dir() -> io::Result<PathBuf> {
+    let path = crate::ue4ss::MOD_DIR.join("trace.jsonl");
+    dump(&path)?;
+    Ok(path)
+}
+
+#[cfg(windows)]
+static TRACE_KEY: Hotkey = Hotkey::new(0x78); // F9
+#[cfg(windows)]
+static DUMP_KEY: Hotkey = Hotkey::new(0x79); // F10
+#[cfg(windows)]
+static RECENT_LOG_KEY: Hotkey = Hotkey::new(0x7a); // F11
+
+/// Debug keys for a mod that wants them, call it from `on_update`. F9 turns tracing on and off,
+/// F10 appends the numbers so far to `trace.jsonl` and F11 saves the log ring to
+/// `logs/recent.log`. Every mod calling it reacts to the same press, each with its own hooks.
+#[cfg(windows)]
+pub fn poll_hotkeys() {
+    if TRACE_KEY.pressed() {
+        info!("tracing: {}", toggle_tracing());
+    }
+    if DUMP_KEY.pressed() {
+        match dump_to_mod_dir() {
+            Ok(path) => info!("trace saved to {}", path.display()),
+            Err(err) => warn!("failed to save trace: {}", err),
+        }
+    }
+    if RECENT_LOG_KEY.pressed()
+        && let Err(err) = crate::ring::LOG_RING.drain_to_mod_dir()
+    {
+        warn!("failed to save recent log: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let stats = HookStats::new();
+        assert_eq!(stats.report("Hook"), None);
+        for us in 1..=100 {
+            stats.record(Duration::from_micros(us));
+        }
+        let report = stats.report("Hook").unwrap();
+        assert_eq!(report.hook, "Hook");
+        assert_eq!(report.calls, 100);
+        assert_eq!(report.min_us, 1.0);
+        assert_eq!(report.avg_us, 50.5);
+        assert_eq!(report.max_us, 100.0);
+        assert_eq!(report.p99_us, 99.0);
+
+        // the p99 only looks at the last `RECENT` calls, everything else is over the whole run
+        for _ in 0..RECENT {
+            stats.record(Duration::from_micros(2));
+        }
+        let report = stats.report("Hook").unwrap();
+        assert_eq!(report.calls, 100 + RECENT as u64);
+        assert_eq!(report.max_us, 100.0);
+        assert_eq!(report.p99_us, 2.0);
+
+        stats.reset();
+        assert_eq!(stats.report("Hook"), None);
+    }
+}
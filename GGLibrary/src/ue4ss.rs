@@ -10,7 +10,10 @@ use std::sync::{Arc, LazyLock};
 use widestring::{U16CString, U16String, WideStr, WideString};
 use winapi::shared::minwindef::{FARPROC, HINSTANCE, HINSTANCE__};
 use winapi::um::errhandlingapi::GetLastError;
-use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
+use winapi::um::libloaderapi::{
+    GetModuleFileNameW, GetModuleHandleExW, GetModuleHandleW, GetProcAddress,
+    GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS, GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+};
 use winapi::um::processthreadsapi::GetCurrentProcess;
 use winapi::um::psapi::{GetModuleInformation, MODULEINFO};
 
@@ -125,6 +128,28 @@ pub static CONFIG_PATH: LazyLock<String> = LazyLock::new(|| {
     }
 });
 
+static MOD_DIR_ANCHOR: u8 = 0;
+
+/// `Mods/<mod>` of the mod this copy of gglibrary is linked into, the dll itself sits in `dlls`
+pub static MOD_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    let mut module = std::ptr::null_mut();
+    let mut buf = [0u16; 1024];
+    let len = unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            &MOD_DIR_ANCHOR as *const u8 as *const u16,
+            &mut module,
+        );
+        GetModuleFileNameW(module, buf.as_mut_ptr(), buf.len() as u32) as usize
+    };
+    let dll = PathBuf::from(U16String::from_vec(&buf[..len]).to_os_string());
+    match dll.parent() {
+        Some(dlls) if dlls.ends_with("dlls") => dlls.parent().unwrap_or(dlls).to_path_buf(),
+        Some(dir) => dir.to_path_buf(),
+        None => PathBuf::new(),
+    }
+});
+
 pub static FMalloc: LazyLock<fn_FMemory_Malloc> = LazyLock::new(|| {
     let addr = unsafe {
         GetProcAddress(
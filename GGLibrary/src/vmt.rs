@@ -0,0 +1,238 @@
+use crate::hooks::{HookError, RegisteredHook};
+use crate::memory::write_protected;
+use std::any::Any;
+use std::ffi::c_void;
+use std::sync::Arc;
+
+/// Where a `VmtHook` swaps the slot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VmtMode {
+    /// the class's vtable, every instance of the class (and subclasses that don't override it)
+    /// goes through the detour
+    #[default]
+    Shared,
+    /// a private copy of the vtable that only this object points at. `slots` is how many the
+    /// object's actual class has (count them in IDA), the `_vtbl` structs usually stop short and
+    /// a virtual call past the end of the copy would read garbage.
+    Instance { slots: usize },
+}
+
+/// A hooked vtable slot, `orig` is whatever the slot held before. Created disabled like `Hook`.
+///
+/// In `Instance` mode the copied vtable is leaked on purpose, the game may still be inside one of
+/// its methods when the hook is removed.
+pub struct VmtHook<F: Copy> {
+    pub orig: F,
+    pub detour: F,
+    slot: *mut F,
+    object: *mut *const c_void,
+    // what the object pointed at before getting a private copy
+    original_vtable: Option<*const c_void>,
+}
+
+unsafe impl<F: Copy> Send for VmtHook<F> {}
+unsafe impl<F: Copy> Sync for VmtHook<F> {}
+
+fn same<F: Copy>(a: F, b: F) -> bool {
+    unsafe { std::mem::transmute_copy::<F, usize>(&a) == std::mem::transmute_copy::<F, usize>(&b) }
+}
+
+impl<F: Copy> VmtHook<F> {
+    pub fn enable(&self) -> Result<(), HookError> {
+        if same(unsafe { self.slot.read_unaligned() }, self.detour) {
+            return Err(HookError::AlreadyEnabled);
+        }
+        unsafe { write_protected(self.slot, self.detour) }.map_err(|err| HookError::Backend(err.to_string()))
+    }
+
+    pub fn disable(&self) -> Result<(), HookError> {
+        if !same(unsafe { self.slot.read_unaligned() }, self.detour) {
+            return Err(HookError::NotEnabled);
+        }
+        unsafe { write_protected(self.slot, self.orig) }.map_err(|err| HookError::Backend(err.to_string()))
+    }
+
+    /// Puts the slot back, and the object's own vtable pointer in `Instance` mode. The object
+    /// has to still be alive.
+    pub fn remove(&self) -> Result<(), HookError> {
+        match self.disable() {
+            Ok(()) | Err(HookError::NotEnabled) => {}
+            Err(err) => return Err(err),
+        }
+        if let Some(vtable) = self.original_vtable {
+            unsafe { self.object.write(vtable) };
+        }
+        Ok(())
+    }
+}
+
+impl<F: Copy + 'static> RegisteredHook for VmtHook<F> {
+    fn enable(&self) -> Result<(), HookError> {
+        VmtHook::enable(self)
+    }
+
+    fn disable(&self) -> Result<(), HookError> {
+        VmtHook::disable(self)
+    }
+
+    fn remove(&self) -> Result<(), HookError> {
+        VmtHook::remove(self)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Hooks one method of `object` through its vtable, described by one of the `red.rs` `_vtbl`
+/// structs. `select` picks the slot without ever making a reference to the vtable, the shared one
+/// is read-only and other threads call through it:
+///
+/// ```ignore
+/// let hook = hook_vmt::<UREDUMGCommonWidget_vtbl, _>(widget, VmtMode::Instance { slots: 98 },
+///     |vtbl| unsafe { &raw mut (*vtbl).on_input_button }, on_input_button)?;
+/// hook.enable()?;
+/// ```
+///
+/// `object` must start with its vtable pointer and `V` must be a prefix of its vtable.
+pub unsafe fn hook_vmt<V, F: Copy>(
+    object: *mut c_void,
+    mode: VmtMode,
+    select: impl FnOnce(*mut V) -> *mut F,
+    detour: F,
+) -> Result<VmtHook<F>, HookError> {
+    const { assert!(size_of::<F>() == size_of::<usize>(), "vtable slots are function pointers") };
+    if object.is_null() {
+        return Err(HookError::NotCreated);
+    }
+    let object = object as *mut *const c_void;
+    let shared = unsafe { object.read() };
+
+    let (vtable, original_vtable) = match mode {
+        VmtMode::Shared => (shared as *mut V, None),
+        VmtMode::Instance { slots } => {
+            assert!(slots * size_of::<usize>() >= size_of::<V>(), "the vtable has more slots than that");
+            // msvc keeps the complete object locator right before the first slot, copy it too
+            let words = slots + 1;
+            let mut copy = vec![0usize; words];
+            unsafe {
+                std::ptr::copy_nonoverlapping((shared as *const usize).sub(1), copy.as_mut_ptr(), words);
+            }
+            let copy = Box::leak(copy.into_boxed_slice());
+            let vtable = unsafe { copy.as_mut_ptr().add(1) } as *mut V;
+            unsafe { object.write(vtable as *const c_void) };
+            (vtable, Some(shared))
+        }
+    };
+
+    let slot = select(vtable);
+    Ok(VmtHook { orig: unsafe { slot.read_unaligned() }, detour, slot, object, original_vtable })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    struct Object {
+        vtable: *const usize,
+    }
+
+    type Method = extern "C" fn(*const Object) -> u32;
+
+    #[repr(C)]
+    struct Object_vtbl {
+        first: Method,
+        second: Method,
+    }
+
+    extern "C" fn first(_this: *const Object) -> u32 {
+        1
+    }
+
+    extern "C" fn second(_this: *const Object) -> u32 {
+        2
+    }
+
+    extern "C" fn third(_this: *const Object) -> u32 {
+        3
+    }
+
+    extern "C" fn detour(_this: *const Object) -> u32 {
+        20
+    }
+
+    const COL: usize = 0xc01;
+
+    // the complete object locator, then three slots where `Object_vtbl` only knows two
+    fn words() -> [usize; 4] {
+        [COL, first as Method as usize, second as Method as usize, third as Method as usize]
+    }
+
+    fn vtable() -> Box<[usize; 4]> {
+        Box::new(words())
+    }
+
+    fn call_second(object: &Object) -> u32 {
+        unsafe { ((*(object.vtable as *const Object_vtbl)).second)(object) }
+    }
+
+    unsafe fn hook(object: &mut Object, mode: VmtMode) -> VmtHook<Method> {
+        let object = object as *mut Object as *mut c_void;
+        unsafe { hook_vmt::<Object_vtbl, Method>(object, mode, |vtbl| &raw mut (*vtbl).second, detour) }.unwrap()
+    }
+
+    #[test]
+    fn shared() {
+        let vtable = vtable();
+        let shared = unsafe { vtable.as_ptr().add(1) };
+        let mut object = Object { vtable: shared };
+        let other = Object { vtable: shared };
+        let hook = unsafe { hook(&mut object, VmtMode::Shared) };
+        assert!(same(hook.orig, second as Method));
+        assert_eq!(call_second(&object), 2);
+
+        hook.enable().unwrap();
+        assert_eq!(hook.enable(), Err(HookError::AlreadyEnabled));
+        assert_eq!(call_second(&object), 20);
+        assert_eq!(call_second(&other), 20);
+        assert_eq!(object.vtable, shared);
+
+        hook.disable().unwrap();
+        assert_eq!(hook.disable(), Err(HookError::NotEnabled));
+        assert_eq!(call_second(&object), 2);
+        assert_eq!(*vtable, words());
+    }
+
+    #[test]
+    fn instance() {
+        let vtable = vtable();
+        let shared = unsafe { vtable.as_ptr().add(1) };
+        let mut object = Object { vtable: shared };
+        let other = Object { vtable: shared };
+        let hook = unsafe { hook(&mut object, VmtMode::Instance { slots: 3 }) };
+        assert_ne!(object.vtable, shared);
+        // the whole class's vtable was copied, locator included
+        let copy = unsafe { std::slice::from_raw_parts(object.vtable.sub(1), 4) };
+        assert_eq!(copy, *vtable);
+
+        hook.enable().unwrap();
+        assert_eq!(hook.enable(), Err(HookError::AlreadyEnabled));
+        assert_eq!(call_second(&object), 20);
+        assert_eq!(call_second(&other), 2);
+        assert_eq!(*vtable, words());
+
+        hook.remove().unwrap();
+        assert_eq!(object.vtable, shared);
+        assert_eq!(call_second(&object), 2);
+        assert_eq!(hook.disable(), Err(HookError::NotEnabled));
+    }
+
+    #[test]
+    fn null_objects() {
+        let result = unsafe {
+            hook_vmt::<Object_vtbl, Method>(std::ptr::null_mut(), VmtMode::Shared, |vtbl| &raw mut (*vtbl).second, detour)
+        };
+        assert_eq!(result.err(), Some(HookError::NotCreated));
+    }
+}
@@ -1,26 +1,47 @@
 use crate::ConfigError::NoneError;
 use enum_map::EnumMap;
-use gglibrary::cxxstd::CxxString;
-use gglibrary::memory::{hook_function, hook_function_from_addr, print_memory, signature_scan, signature_scan_from_addr, Hook, ThreadSafePtr};
-use gglibrary::output::{budget_log, clear_log};
+use gglibrary::global::GameGlobal;
+use gglibrary::output::budget_log;
+use gglibrary::sigtable::SignatureTable;
+use gglibrary::reflect::dump;
 use gglibrary::red::{AREDGameState_CharaSelect, EBattleCharaSpFlag, ECharaID, EColorID, ECostumeID, Packet_BattleReady, SDecideInfoHistory};
-use gglibrary::ue4ss::{CppUserModBase, FMalloc, FString, ModCallback, CONFIG_PATH};
-use libc::memcpy;
-use minhook::MinHook;
 use rand::seq::IndexedRandom;
 use serde::Deserialize;
-use std::alloc::Layout;
 use std::ffi::c_void;
 use std::fmt::{format, Debug};
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::marker::PhantomData;
 use std::mem::offset_of;
 use std::num::ParseIntError;
 use std::str::FromStr;
-use std::sync::OnceLock;
+use std::sync::{LazyLock, OnceLock};
 use strum::{IntoEnumIterator, ParseError};
 use toml::{de, Table};
+#[cfg(any(windows, test))]
+use gglibrary::hooks::HookRegistry;
+
+// everything that needs UE4SS or the game's allocator, the rest builds and is tested anywhere
+#[cfg(windows)]
+use gglibrary::cxxstd::CxxString;
+#[cfg(windows)]
+use gglibrary::memory::resolve_signatures;
+#[cfg(windows)]
+use gglibrary::output::{mod_logger, Ue4ssSink};
+#[cfg(windows)]
+use gglibrary::trace;
+#[cfg(windows)]
+use gglibrary::ue4ss::{CppUserModBase, FMalloc, FString, ModCallback, CONFIG_PATH};
+#[cfg(windows)]
+use libc::memcpy;
+#[cfg(windows)]
+use log::LevelFilter;
+#[cfg(windows)]
+use std::alloc::Layout;
+#[cfg(windows)]
+use std::fs::OpenOptions;
+#[cfg(windows)]
+use std::io::Write;
+#[cfg(windows)]
+use std::marker::PhantomData;
+#[cfg(windows)]
 use widestring::U16CString;
 
 #[derive(Debug)]
@@ -49,11 +70,11 @@ impl From<ParseError> for ConfigError {
     }
 }
 
-fn is_color_allowed_for_config(char_id: ECharaID, color_id: EColorID) -> bool { // hook must be disabled
+fn is_color_allowed_for_config(char_id: ECharaID, color_id: EColorID) -> bool {
     if color_id == EColorID(72) {
         return false;
     }
-    unsafe { (HOOKS.get().unwrap().IsSelectableCharaColorID.target)(char_id , color_id) }
+    unsafe { IsSelectableCharaColorID::orig()(char_id , color_id) }
 }
 
 type Config = EnumMap<ECharaID, Vec<EColorID>>;
@@ -103,114 +124,127 @@ pub fn create_config() -> Config {
     config
 }
 
-type fn_UREDWidgetSimpleCharaSelect_OnInputPressTrigger = unsafe extern "C" fn(*mut c_void, u32);
-type fn_IsSelectableCharaColorID = unsafe extern "C" fn(ECharaID, EColorID) -> bool;
-type fn_SendBattleReady = unsafe extern "C" fn(bool) -> bool;
-type fn_SendPacket = unsafe extern "C" fn(u32, *mut gglibrary::red::Header, *mut c_void) -> bool;
-type fn_ColorIdToDisplayNumber = unsafe extern "C" fn(*mut FString, EColorID) -> *mut FString;
-type fn_IsAllowedCharaColorID = unsafe extern "C" fn(ECharaID, EColorID) -> bool;
-// AREDPawnCharaSelect::UpdateCharaAsset
-type fn_UpdateCharaAsset = unsafe extern "C" fn(*mut c_void, ECharaID, EColorID, ECostumeID, EBattleCharaSpFlag, u32, bool);
-type fn_GotoBattleSetting = unsafe extern "C" fn(*mut c_void);
-
-#[allow(non_snake_case)]
-struct Hooks {
-    UREDWidgetSimpleCharaSelect_OnInputPressTrigger: Hook<fn_UREDWidgetSimpleCharaSelect_OnInputPressTrigger>,
-    IsSelectableCharaColorID: Hook<fn_IsSelectableCharaColorID>,
-    SendBattleReady: Hook<fn_SendBattleReady>,
-    SendPacket: Hook<fn_SendPacket>,
-    ColorIdToDisplayNumber: Hook<fn_ColorIdToDisplayNumber>,
-    IsAllowedCharaColorID: Hook<fn_IsAllowedCharaColorID>,
-    UpdateCharaAsset: Hook<fn_UpdateCharaAsset>,
-    GotoBattleSetting: Hook<fn_GotoBattleSetting>,
-    CharaHistory: Option<ThreadSafePtr<c_void>>,
-}
+// set up when the scan found it and it looked sane
+static CHARA_HISTORY: GameGlobal<SDecideInfoHistory> =
+    GameGlobal::new("CharaHistory", r#"entry("CharaHistory")"#, || TABLE.as_ref())
+        .validate(|history| history.is_valid == 1); // a bool in game
 
-type Data = PhantomData<Hooks>;
-static HOOKS: OnceLock<Hooks> = OnceLock::new();
+#[cfg(windows)]
+type Data = PhantomData<()>;
 
-pub unsafe extern "C" fn input_press(this: *mut c_void, flag: u32) {
-    budget_log(("press: ".to_string() + flag.to_string().as_str() + "\n").as_str());
-    let hooks = HOOKS.get().unwrap();
-    (hooks.UREDWidgetSimpleCharaSelect_OnInputPressTrigger.orig)(this, flag);
-}
+#[cfg(any(windows, test))]
+static HOOK_REGISTRY: HookRegistry = HookRegistry::new();
 
-pub unsafe extern "C" fn is_selectable_chara_color_id(char_id: ECharaID, color_id: EColorID) -> bool {
-    if color_id == EColorID(72) {
-        return true;
+gglibrary::hooks! {
+    fn install_hooks;
+
+    #[hook(UREDWidgetSimpleCharaSelect_OnInputPressTrigger)]
+    pub unsafe extern "C" fn input_press(this: *mut c_void, flag: u32) {
+        budget_log(("press: ".to_string() + flag.to_string().as_str() + "\n").as_str());
+        UREDWidgetSimpleCharaSelect_OnInputPressTrigger::orig()(this, flag);
     }
-    let hooks = HOOKS.get().unwrap();
-    (hooks.IsSelectableCharaColorID.orig)(char_id, color_id)
-}
 
-pub unsafe extern "C" fn color_id_to_display_number(result: *mut FString, color_id: EColorID) -> *mut FString {
-    if color_id == EColorID(72) {
-        let ptr = (*FMalloc)(2 * 4, 0);
-        memcpy(ptr, U16CString::from_str("RND").unwrap().as_mut_ptr().cast(), 2 * 3 + 1);
-        let fstring = &mut *result;
-        fstring.data = ptr.cast();
-        fstring.size = 4;
-        fstring.capacity = 4;
-        result
+    #[hook(IsSelectableCharaColorID)]
+    pub unsafe extern "C" fn is_selectable_chara_color_id(char_id: ECharaID, color_id: EColorID) -> bool {
+        if color_id == EColorID(72) {
+            return true;
+        }
+        IsSelectableCharaColorID::orig()(char_id, color_id)
     }
-    else {
-        let hooks = HOOKS.get().unwrap();
-        (hooks.ColorIdToDisplayNumber.orig)(result, color_id)
+
+    #[hook(IsAllowedCharaColorID)]
+    pub unsafe extern "C" fn is_allowed_chara_color_id(char_id: ECharaID, color_id: EColorID) -> bool {
+        if color_id == EColorID(72) {
+            return true;
+        }
+        IsAllowedCharaColorID::orig()(char_id, color_id)
     }
-}
 
-pub unsafe extern "C" fn is_allowed_chara_color_id(char_id: ECharaID, color_id: EColorID) -> bool {
-    if color_id == EColorID(72) {
-        return true;
+    // AREDPawnCharaSelect::UpdateCharaAsset
+    #[hook(UpdateCharaAsset)]
+    pub unsafe extern "C" fn update_chara_asset(
+        this: *mut c_void,
+        char_id: ECharaID,
+        color_id: EColorID,
+        costume_id: ECostumeID,
+        sp_flag: EBattleCharaSpFlag,
+        side: u32,
+        is_silhouette: bool,
+    ) {
+        if color_id == EColorID(72) {
+            return UpdateCharaAsset::orig()(this, char_id, EColorID(0), costume_id, sp_flag, side, true);
+        }
+        UpdateCharaAsset::orig()(this, char_id, color_id, costume_id, sp_flag, side, is_silhouette);
     }
-    let hooks = HOOKS.get().unwrap();
-    (hooks.IsAllowedCharaColorID.orig)(char_id, color_id)
-}
 
+    #[hook(GotoBattleSetting)]
+    pub unsafe extern "C" fn goto_battle_setting(this: *mut c_void) {
+        budget_log("goto_battle_setting");
+        let mut chara_select = (this as *mut AREDGameState_CharaSelect).as_mut().unwrap();
+
+        let mut is_rand: [bool; 2] = [false, false];
+        for  i in 0..2 {
+            let side_info = &mut chara_select.side_info[i];
+            budget_log(format!("chara: {:?}, color: {:?}", side_info.decide_info.chara_id, side_info.decide_info.color_id).as_str());
+            if side_info.decide_info.color_id == EColorID(72) {
+                side_info.decide_info.color_id = get_random_color(side_info.decide_info.chara_id);
+                is_rand[i] = true;
+            }
+        }
+        GotoBattleSetting::orig()(this);
+        // the offset is a guess, `get_mut` makes sure it's writable before touching it
+        if let Some(decide_history) = CHARA_HISTORY.get_mut() {
+            budget_log(dump(decide_history).as_str());
+            for i in 0..2 {
+                let decide_info = &mut decide_history.chara_history[i];
+                if is_rand[i] {
+                    decide_info.color_id = EColorID(72);
+                }
+            }
+        }
+    }
 
-pub unsafe extern "C" fn update_chara_asset(
-    this: *mut c_void,
-    char_id: ECharaID,
-    color_id: EColorID,
-    costume_id: ECostumeID,
-    sp_flag: EBattleCharaSpFlag,
-    side: u32,
-    is_silhouette: bool,
-) {
-    let hooks = HOOKS.get().unwrap();
-    if color_id == EColorID(72) {
-        return (hooks.UpdateCharaAsset.orig)(this, char_id, EColorID(0), costume_id, sp_flag, side, true);
+    #[hook(SendBattleReady)]
+    pub unsafe extern "C" fn send_battle_ready(battle_ready: bool) -> bool {
+        budget_log("send_battle_ready");
+        SendBattleReady::orig()(battle_ready)
     }
-    (hooks.UpdateCharaAsset.orig)(this, char_id, color_id, costume_id, sp_flag, side, is_silhouette);
-}
 
-pub unsafe extern "C" fn goto_battle_setting(this: *mut c_void) {
-    let hooks = HOOKS.get().unwrap();
-    budget_log("goto_battle_setting");
-    let mut chara_select = (this as *mut AREDGameState_CharaSelect).as_mut().unwrap();
-
-    let mut is_rand: [bool; 2] = [false, false];
-    for  i in 0..2 {
-        let side_info = &mut chara_select.side_info[i];
-        budget_log(format!("chara: {:?}, color: {:?}", side_info.decide_info.chara_id, side_info.decide_info.color_id).as_str());
-        if side_info.decide_info.color_id == EColorID(72) {
-            side_info.decide_info.color_id = get_random_color(side_info.decide_info.chara_id);
-            is_rand[i] = true;
+    #[hook(SendPacket)]
+    pub unsafe extern "C" fn send_packet(socket_type: u32, header: *mut gglibrary::red::Header, peer_handle: *mut c_void) -> bool {
+        if (*header).packet_type == 0x32 {
+            let battle_ready: *mut Packet_BattleReady = std::mem::transmute(header);
+            let battle_ready = &mut *battle_ready;
+            for i in 0..3 {
+                if battle_ready.color[i] == 72 {
+                    let color = get_random_color(ECharaID::from_repr(battle_ready.chara[i] as u32).unwrap()).0 as i8;
+                    battle_ready.color[i] = color;
+                }
+            }
         }
+
+        SendPacket::orig()(socket_type, header, peer_handle)
     }
-    (hooks.GotoBattleSetting.orig)(this);
-    if let Some(chara_history) = &hooks.CharaHistory {
-        budget_log(print_memory(chara_history.cast(), size_of::<SDecideInfoHistory>()).as_str());
-        let decide_history: *mut SDecideInfoHistory = std::mem::transmute(chara_history.0);
+}
 
-        if !(*decide_history).is_valid { // should be true
-            return;
+// allocates the string through UE4SS's FMalloc
+#[cfg(windows)]
+gglibrary::hooks! {
+    fn install_display_hooks;
+
+    #[hook(ColorIdToDisplayNumber)]
+    pub unsafe extern "C" fn color_id_to_display_number(result: *mut FString, color_id: EColorID) -> *mut FString {
+        if color_id == EColorID(72) {
+            let ptr = (*FMalloc)(2 * 4, 0);
+            memcpy(ptr, U16CString::from_str("RND").unwrap().as_mut_ptr().cast(), 2 * 3 + 1);
+            let fstring = &mut *result;
+            fstring.data = ptr.cast();
+            fstring.size = 4;
+            fstring.capacity = 4;
+            result
         }
-        for i in 0..2 {
-            let decide_info = &mut (*decide_history).chara_history[i];
-            if is_rand[i] {
-                decide_info.color_id = EColorID(72);
-            }
+        else {
+            ColorIdToDisplayNumber::orig()(result, color_id)
         }
     }
 }
@@ -223,53 +257,61 @@ pub fn dummy_config() -> Config {
     config
 }
 
+#[cfg(windows)]
 const CONFIG_SUFFIX: &str = "\n# use \"1-4\" to add colors 1 through 4 inclusively\n# a higher frequency will correspond to a higher weight \n# KYK = [1,14,\"1-3\"], will have a 40% chance of picking color 1 and and a 20% change of picking colors 14, 2, or 3";
 
 pub fn get_config() -> &'static Config {
-    HOOKS.get().unwrap().IsSelectableCharaColorID.disable();
-    let conf = CONFIG.get_or_init(|| {
-        let config_path = (*CONFIG_PATH).as_str();
-        budget_log(format!("config: {}", config_path).as_str());
-        if !std::fs::exists(config_path).unwrap() {
-            let config = create_config();
-            let mut file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .open(config_path);
-            if file.is_err() {
-                budget_log("failed to open config file");
-                budget_log(format!("{:?}", file.err().unwrap()).as_str());
+    let conf = CONFIG.get_or_init(load_config);
+    budget_log(format!("{:?}", conf).as_str());
+    conf
+}
+
+#[cfg(windows)]
+fn load_config() -> Config {
+    let config_path = (*CONFIG_PATH).as_str();
+    budget_log(format!("config: {}", config_path).as_str());
+    if !std::fs::exists(config_path).unwrap() {
+        let config = create_config();
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(config_path);
+        if file.is_err() {
+            budget_log("failed to open config file");
+            budget_log(format!("{:?}", file.err().unwrap()).as_str());
+            dummy_config()
+        } else {
+            let table = toml::to_string(&config);
+            if table.is_err() {
+                budget_log("failed to serialize config");
+                budget_log(format!("{:?}", table.err().unwrap()).as_str());
                 dummy_config()
             } else {
-                let table = toml::to_string(&config);
-                if table.is_err() {
-                    budget_log("failed to serialize config");
-                    budget_log(format!("{:?}", table.err().unwrap()).as_str());
-                    dummy_config()
-                } else {
-                    file.unwrap().write((table.unwrap() + CONFIG_SUFFIX).as_bytes()).unwrap();
-                    config
-                }
+                file.unwrap().write((table.unwrap() + CONFIG_SUFFIX).as_bytes()).unwrap();
+                config
             }
+        }
+    } else {
+        let file = std::fs::read_to_string(config_path);
+        if file.is_err() {
+            budget_log("failed to read config file");
+            budget_log(format!("{:?}", file.err().unwrap()).as_str());
+            dummy_config()
         } else {
-            let file = std::fs::read_to_string(config_path);
-            if file.is_err() {
-                budget_log("failed to read config file");
-                budget_log(format!("{:?}", file.err().unwrap()).as_str());
+            budget_log("hello");
+            parse_config(file.unwrap().as_str()).unwrap_or_else(|err| {
+                budget_log("failed to parse config");
+                budget_log(format!("{:?}", err).as_str());
                 dummy_config()
-            } else {
-                budget_log("hello");
-                parse_config(file.unwrap().as_str()).unwrap_or_else(|err| {
-                    budget_log("failed to parse config");
-                    budget_log(format!("{:?}", err).as_str());
-                    dummy_config()
-                })
-            }
+            })
         }
-    });
-    budget_log(format!("{:?}", conf).as_str());
-    HOOKS.get().unwrap().IsSelectableCharaColorID.enable();
-    conf
+    }
+}
+
+// no UE4SS to say where the config goes
+#[cfg(not(windows))]
+fn load_config() -> Config {
+    dummy_config()
 }
 
 fn get_random_color(chara: ECharaID) -> EColorID {
@@ -277,88 +319,89 @@ fn get_random_color(chara: ECharaID) -> EColorID {
     color
 }
 
-pub unsafe extern "C" fn send_battle_ready(battle_ready: bool) -> bool {
-    let hooks = HOOKS.get().unwrap();
-    budget_log("send_battle_ready");
-    (hooks.SendBattleReady.orig)(battle_ready)
-}
-
-pub unsafe extern "C" fn send_packet(socket_type: u32, header: *mut gglibrary::red::Header, peer_handle: *mut c_void) -> bool {
-    let hooks = HOOKS.get().unwrap();
-    if (*header).packet_type == 0x32 {
-        let battle_ready: *mut Packet_BattleReady = std::mem::transmute(header);
-        let battle_ready = &mut *battle_ready;
-        for i in 0..3 {
-            if battle_ready.color[i] == 72 {
-                let color = get_random_color(ECharaID::from_repr(battle_ready.chara[i] as u32).unwrap()).0 as i8;
-                battle_ready.color[i] = color;
-            }
-        }
-    }
+static CONFIG: OnceLock<Config> = OnceLock::new();
 
-    (hooks.SendPacket.orig)(socket_type, header, peer_handle)
-}
+static SIGNATURES: &str = include_str!("../signatures.toml");
 
-static CONFIG: OnceLock<Config> = OnceLock::new();
+static TABLE: LazyLock<Option<SignatureTable>> = LazyLock::new(|| {
+    SignatureTable::from_toml(SIGNATURES)
+        .inspect_err(|err| budget_log(format!("{}", err).as_str()))
+        .ok()
+});
 
-pub unsafe extern "C" fn on_unreal_init(this: *mut CppUserModBase<Data>) {
-    budget_log("unreal_init");
+#[cfg(windows)]
+gglibrary::contained! {
+    pub unsafe extern "C" fn on_unreal_init(this: *mut CppUserModBase<Data>) {
+        budget_log("unreal_init");
 
-    let addr = signature_scan("48 89 5c 24 ? 48 89 74 24 ? 48 89 7c 24 ? 55 41 54 41 55 41 56 41 57 48 8d 6c 24 ? 48 81 ec ? ? ? ? 48 8b 05 ? ? ? ? 48 33 c4 48 89 45 ? c6 05").unwrap();
-    // mov rip+disp32 01
-    // C6 05 DC CC 9E 03 01;
-    let mut chara_history_inst = signature_scan_from_addr("C6 05 ? ? ? ? 01", addr);
+        let Some(table) = TABLE.as_ref() else {
+            return;
+        };
+
+        // one sweep for everything, report every missing signature before giving up
+        let addrs = resolve_signatures(table);
+        let missing: Vec<&str> = addrs
+            .iter()
+            .filter(|(name, addr)| addr.is_err() && *name != "CharaHistory")
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            budget_log(format!("not hooking anything, missing: {}", missing.join(", ")).as_str());
+            return;
+        }
+        let addr = *addrs["GotoBattleSetting"].as_ref().unwrap();
+
+        let chara_history = addrs["CharaHistory"].clone().ok().filter(|&history| {
+            let distance = history as isize - addr as isize;
+            (1000000..=100000000).contains(&distance) // stupid sanity check
+        });
+        match chara_history {
+            Some(history) => {
+                CHARA_HISTORY.init_at(history as usize);
+            }
+            None => CHARA_HISTORY.disable(),
+        }
 
-    if let Some(inst) = chara_history_inst {
-        let offset = (inst.offset(2) as *mut u32).read_unaligned();
-        if offset < 1000000 || offset > 100000000 { // stupid sanity check
-            chara_history_inst = None;
-        } else {
-            chara_history_inst = Some(inst.offset(offset as isize + 7 - 0x40));
+        let mut failed = install_hooks(&HOOK_REGISTRY, &addrs).err().unwrap_or_default();
+        failed.extend(install_display_hooks(&HOOK_REGISTRY, &addrs).err().unwrap_or_default());
+        if !failed.is_empty() {
+            budget_log(format!("failed to hook: {}", failed.join(", ")).as_str());
         }
     }
+}
 
-    HOOKS.set(Hooks {
-        UREDWidgetSimpleCharaSelect_OnInputPressTrigger: hook_function::<fn_UREDWidgetSimpleCharaSelect_OnInputPressTrigger>(
-            "40 53 48 83 ec ?? 8b 81 ?? ?? ?? ?? 48 8b d9 85 c0 0f 85",
-            input_press).unwrap(),
-        IsSelectableCharaColorID: hook_function::<fn_IsSelectableCharaColorID>(
-            "48 89 5c 24 ?? 48 89 74 24 ?? 48 89 7c 24 ?? 55 41 54 41 55 41 56 41 57 48 8b ec 48 83 ec ?? 45 33 ed 8b fa",
-            is_selectable_chara_color_id).unwrap(),
-        SendBattleReady: hook_function::<fn_SendBattleReady>(
-            "4c 8b dc 55 49 8d ab ? ? ? ? 48 81 ec ? ? ? ? 48 8b 05 ? ? ? ? 48 33 c4 48 89 85 ? ? ? ? 49 89 5b ? 0f b6 d9",
-            send_battle_ready).unwrap(),
-        SendPacket: hook_function::<fn_SendPacket>(
-            "4d 8b c8 4c 8b c2 8b d1 48 8b 0d ? ? ? ? e9",
-            send_packet).unwrap(),
-        ColorIdToDisplayNumber: hook_function::<fn_ColorIdToDisplayNumber>(
-            "40 53 48 83 ec ? 48 8b d9 83 fa ? 74 ? 83 fa ? 74",
-            color_id_to_display_number).unwrap(),
-        IsAllowedCharaColorID: hook_function::<fn_IsAllowedCharaColorID>(
-            "83 fa ? 76 ? 83 fa ? 75",
-            is_allowed_chara_color_id).unwrap(),
-        UpdateCharaAsset: hook_function::<fn_UpdateCharaAsset>(
-            "4c 8b dc 45 89 4b ? 55 53",
-            update_chara_asset).unwrap(),
-        GotoBattleSetting: hook_function_from_addr::<fn_GotoBattleSetting>(
-            addr as *mut c_void,
-            goto_battle_setting).unwrap(),
-        CharaHistory: chara_history_inst.map(|inst| {ThreadSafePtr(inst as *mut c_void)}),
-    });
+// the detours live in this dll, they can't outlive it
+#[cfg(windows)]
+fn remove_hooks() {
+    for (name, err) in HOOK_REGISTRY.remove_all() {
+        budget_log(format!("failed to remove {}: {}", name, err).as_str());
+    }
+}
+
+#[cfg(windows)]
+gglibrary::contained! {
+    pub unsafe extern "C" fn on_destroy(_: *mut CppUserModBase<Data>) {
+        remove_hooks();
+    }
+}
 
-    let res = MinHook::enable_all_hooks();
-    if res.is_err() {
-        budget_log("enable all hooks failed");
-        budget_log(format!("{:?}", res.unwrap_err()).as_str());
-        return;
+#[cfg(windows)]
+gglibrary::contained! {
+    // F9/F10/F11 for tracing and the log ring
+    pub unsafe extern "C" fn on_update(_: *mut CppUserModBase<Data>) {
+        trace::poll_hotkeys();
     }
 }
 
+#[cfg(windows)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn start_mod() -> *mut CppUserModBase<Data> {
-    clear_log();
+    // only fails if it was already set up
+    let _ = mod_logger().level(LevelFilter::Debug).sink(Ue4ssSink::new()).init();
     let vtable = Box::new(gglibrary::ue4ss::Vtable {
         on_unreal_init: ModCallback(on_unreal_init),
+        on_update: ModCallback(on_update),
+        destructor: ModCallback(on_destroy),
         ..Default::default()
     });
 
@@ -376,8 +419,127 @@ pub unsafe extern "C" fn start_mod() -> *mut CppUserModBase<Data> {
     }
 }
 
+#[cfg(windows)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn uninstall_mod(cpp_mod: *mut CppUserModBase<Data>) {
+    remove_hooks();
     std::ptr::drop_in_place(cpp_mod);
     std::alloc::dealloc(cpp_mod.cast(), Layout::new::<CppUserModBase<Data>>());
-}
\ No newline at end of file
+}
+
+// the detours against fake game state, `hooks!` goes through gglibrary's mock backend here
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use gglibrary::hooks::MOCK_BACKEND;
+    use gglibrary::region::FAKE_REGIONS;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const RANDOM: EColorID = EColorID(72);
+
+    // where the fake GotoBattleSetting saves what was picked, like the game does
+    static HISTORY: AtomicUsize = AtomicUsize::new(0);
+    static SENT: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn game_goto_battle_setting(this: *mut c_void) {
+        let state = unsafe { &*(this as *const AREDGameState_CharaSelect) };
+        let history = unsafe { &mut *(HISTORY.load(Ordering::Relaxed) as *mut SDecideInfoHistory) };
+        for i in 0..2 {
+            history.chara_history[i].chara_id = state.side_info[i].decide_info.chara_id;
+            history.chara_history[i].color_id = state.side_info[i].decide_info.color_id;
+        }
+    }
+
+    unsafe extern "C" fn game_send_packet(_: u32, _: *mut gglibrary::red::Header, _: *mut c_void) -> bool {
+        SENT.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    // every character only has one color, `chara_id + 1`, so the random pick is known
+    fn installed() {
+        static INSTALL: OnceLock<Result<(), Vec<&'static str>>> = OnceLock::new();
+        let result = INSTALL.get_or_init(|| {
+            CONFIG.get_or_init(|| {
+                let mut config = EnumMap::default();
+                for char_id in ECharaID::iter() {
+                    config[char_id] = vec![EColorID(char_id as u32 + 1)];
+                }
+                config
+            });
+
+            let history: &mut SDecideInfoHistory = Box::leak(unsafe { Box::new_zeroed().assume_init() });
+            history.is_valid = 1;
+            FAKE_REGIONS.insert_slice(std::slice::from_ref(history), true);
+            let history = history as *mut SDecideInfoHistory as usize;
+            HISTORY.store(history, Ordering::Relaxed);
+            CHARA_HISTORY.init_at(history);
+
+            let addrs: BTreeMap<String, Result<*mut u8, _>> = [
+                ("GotoBattleSetting", game_goto_battle_setting as *mut u8),
+                ("SendPacket", game_send_packet as *mut u8),
+            ]
+            .into_iter()
+            .map(|(name, addr)| (name.to_string(), Ok(addr)))
+            .collect();
+            install_hooks(&HOOK_REGISTRY, &addrs)
+        });
+        // only the two under test have an address
+        let failed = [
+            "UREDWidgetSimpleCharaSelect_OnInputPressTrigger",
+            "IsSelectableCharaColorID",
+            "IsAllowedCharaColorID",
+            "UpdateCharaAsset",
+            "SendBattleReady",
+        ];
+        assert_eq!(*result, Err(failed.to_vec()));
+    }
+
+    // what the game would run if it called `target` right now
+    fn call<T: Copy>(target: T) -> T {
+        let target = unsafe { std::mem::transmute_copy::<T, *mut c_void>(&target) };
+        assert!(MOCK_BACKEND.is_enabled(target));
+        unsafe { std::mem::transmute_copy(&MOCK_BACKEND.effective(target)) }
+    }
+
+    #[test]
+    fn goto_battle_setting_picks_random_colors() {
+        installed();
+        let mut state: Box<AREDGameState_CharaSelect> = unsafe { Box::new_zeroed().assume_init() };
+        state.side_info[0].decide_info.chara_id = ECharaID::KYK;
+        state.side_info[0].decide_info.color_id = RANDOM;
+        state.side_info[1].decide_info.chara_id = ECharaID::MAY;
+        state.side_info[1].decide_info.color_id = EColorID(5);
+
+        let goto_battle_setting = call(game_goto_battle_setting as GotoBattleSetting::Type);
+        unsafe { goto_battle_setting(&mut *state as *mut AREDGameState_CharaSelect as *mut c_void) };
+
+        // the game got the picked color, its history still says random so it's random next time
+        assert_eq!(state.side_info[0].decide_info.color_id, EColorID(ECharaID::KYK as u32 + 1));
+        assert_eq!(state.side_info[1].decide_info.color_id, EColorID(5));
+        let history = unsafe { &*(HISTORY.load(Ordering::Relaxed) as *const SDecideInfoHistory) };
+        assert_eq!(history.chara_history[0].color_id, RANDOM);
+        assert_eq!(history.chara_history[1].color_id, EColorID(5));
+    }
+
+    #[test]
+    fn send_packet_picks_random_colors() {
+        installed();
+        let send_packet = call(game_send_packet as SendPacket::Type);
+        let mut packet: Box<Packet_BattleReady> = unsafe { Box::new_zeroed().assume_init() };
+        let header = &mut *packet as *mut Packet_BattleReady as *mut gglibrary::red::Header;
+        packet.chara = [ECharaID::KYK as i8, ECharaID::MAY as i8, ECharaID::SOL as i8];
+        packet.color = [72, 3, 72];
+
+        // only battle ready packets are touched
+        unsafe { (*header).packet_type = 0x31 };
+        let sent = SENT.load(Ordering::Relaxed);
+        assert!(unsafe { send_packet(0, header, std::ptr::null_mut()) });
+        assert_eq!(packet.color, [72, 3, 72]);
+
+        unsafe { (*header).packet_type = 0x32 };
+        assert!(unsafe { send_packet(0, header, std::ptr::null_mut()) });
+        assert_eq!(packet.color, [ECharaID::KYK as i8 + 1, 3, ECharaID::SOL as i8 + 1]);
+        assert_eq!(SENT.load(Ordering::Relaxed), sent + 2);
+    }
+}